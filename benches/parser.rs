@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use properties_builder::document::Document;
+use properties_builder::model::LineEnding;
+use properties_builder::properties_document::PropertiesDocument;
+use std::fmt::Write;
+
+const LINES: usize = 500_000;
+
+/// Generates a properties file mixing comments, blank lines, plain, escaped and continued
+/// properties in roughly the proportions of real configuration files.
+fn generate_input(lines: usize) -> String {
+    let mut input = String::with_capacity(lines * 40);
+    let mut line = 0;
+    while line < lines {
+        let i = line / 10;
+        write!(
+            input,
+            "# comment {i}\n\
+             service.{i}.host=host-{i}.example.com\n\
+             service.{i}.port = {i}\n\
+             service.{i}.path : /api/v{i}/resource\n\
+             service.{i}.name=caf\\u00e9 {i}\n\
+             \n\
+             service.{i}.description=first part \\\n    second part\n\
+             ! other comment\n\
+             service.{i}.enabled true\n"
+        )
+        .unwrap();
+        line += 10;
+    }
+    input
+}
+
+fn parse(c: &mut Criterion) {
+    let input = generate_input(LINES);
+    let mut group = c.benchmark_group("parser");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    group.sample_size(10);
+    group.bench_function("parse", |b| {
+        b.iter(|| PropertiesDocument::parse(black_box(&input)).unwrap())
+    });
+    let document = PropertiesDocument::parse(&input).unwrap();
+    group.bench_function("write", |b| {
+        b.iter(|| {
+            let mut output: Vec<u8> = Vec::with_capacity(input.len());
+            document.write(&mut output, LineEnding::Lf).unwrap();
+            output
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,239 @@
+use crate::document::{parse_document, Document};
+use crate::json_document::JsonDocument;
+use crate::keys::{key_segments, segments_key};
+use crate::model::{Format, InternalError, LineEnding, Property};
+use std::collections::HashSet;
+
+/// A new document of the given format made of `properties`, in their order, or the reason why
+/// it cannot be created. JSON documents are flat objects whose members are named after the keys.
+fn build(
+    properties: &[Property<'static>],
+    format: Format,
+) -> Result<Box<dyn Document<'static>>, String> {
+    let mut document = match format {
+        Format::Json => Box::new(JsonDocument::flat()),
+        _ => parse_document("", format)
+            .map_err(|error| format!("an empty document cannot be created ({})", error))?,
+    };
+    for property in properties {
+        document
+            .push(property.clone())
+            .map_err(|error| match error {
+                InternalError::UnrepresentableProperty { reason, .. } => reason,
+                other => other.to_string(),
+            })?;
+    }
+    Ok(document)
+}
+
+/// The properties read back from the text of `document`, sorted by key and value, or the reason
+/// why they cannot be read.
+fn read_back(
+    document: &dyn Document<'static>,
+    format: Format,
+) -> Result<Vec<(String, String)>, String> {
+    let mut output: Vec<u8> = Vec::new();
+    document
+        .write(&mut output, LineEnding::Lf)
+        .map_err(|error| format!("the output cannot be written ({})", error))?;
+    let text =
+        String::from_utf8(output).map_err(|_| "the output is not valid UTF-8".to_string())?;
+    let mut pairs: Vec<(String, String)> = parse_document(&text, format)
+        .map_err(|error| format!("the output cannot be read back ({})", error))?
+        .properties()
+        .into_iter()
+        .map(|(_, property)| (property.key.into_owned(), property.value.into_owned()))
+        .collect();
+    pairs.sort();
+    Ok(pairs)
+}
+
+fn sorted_pairs(properties: &[Property<'static>]) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = properties
+        .iter()
+        .map(|property| (property.key.to_string(), property.value.to_string()))
+        .collect();
+    pairs.sort();
+    pairs
+}
+
+/// Finds the first key which is both a value and the parent of other keys, which formats nesting
+/// keys into objects cannot represent, with the description of the conflict.
+fn nesting_conflict(properties: &[Property<'static>]) -> Option<(String, String)> {
+    let mut values: HashSet<String> = HashSet::new();
+    let mut parents: HashSet<String> = HashSet::new();
+    for property in properties {
+        let segments = key_segments(&property.key);
+        let key = segments_key(&segments);
+        let ancestors: Vec<String> = (1..segments.len())
+            .map(|len| segments_key(&segments[..len]))
+            .collect();
+        if let Some(ancestor) = ancestors.iter().find(|ancestor| values.contains(*ancestor)) {
+            let reason = format!("it is nested under the value of '{}'", ancestor);
+            return Some((property.key.to_string(), reason));
+        }
+        if parents.contains(&key) {
+            let reason = "it has a value and nested properties".to_string();
+            return Some((property.key.to_string(), reason));
+        }
+        values.insert(key);
+        parents.extend(ancestors);
+    }
+    None
+}
+
+/// A new document of the given format made of `properties`, provided that reading it back
+/// produces exactly the same properties, or the reason why it does not.
+fn written(
+    properties: &[Property<'static>],
+    format: Format,
+) -> Result<Box<dyn Document<'static>>, String> {
+    let document = build(properties, format)?;
+    let pairs = read_back(document.as_ref(), format)?;
+    if pairs == sorted_pairs(properties) {
+        return Ok(document);
+    }
+    Err(match pairs.as_slice() {
+        [] => "it is not read back".to_string(),
+        [(key, value)] => format!("it is read back as '{}={}'", key, value),
+        _ => format!("it is read back as {} properties", pairs.len()),
+    })
+}
+
+/// Converts `properties` into a new document of the given format, verifying that reading the
+/// document back produces exactly the same properties. Fails with
+/// `InternalError::UnrepresentableProperty` naming the first property which is lost or altered,
+/// such as a key which is not valid in the format, or a key which is both a value and the parent
+/// of other keys in formats nesting keys into objects.
+pub fn convert(
+    properties: Vec<Property<'static>>,
+    format: Format,
+) -> Result<Box<dyn Document<'static>>, InternalError> {
+    if matches!(format, Format::Yaml | Format::Toml | Format::Hocon) {
+        if let Some((key, reason)) = nesting_conflict(&properties) {
+            return Err(InternalError::UnrepresentableProperty {
+                key,
+                format,
+                reason,
+            });
+        }
+    }
+    let reason = match written(&properties, format) {
+        Ok(document) => return Ok(document),
+        Err(reason) => reason,
+    };
+    for property in &properties {
+        if let Err(reason) = written(std::slice::from_ref(property), format) {
+            return Err(InternalError::UnrepresentableProperty {
+                key: property.key.to_string(),
+                format,
+                reason,
+            });
+        }
+    }
+    // every property is representable alone, so the first one lost when the properties are
+    // written together conflicts with another
+    for len in 2..=properties.len() {
+        if written(&properties[..len], format).is_err() {
+            return Err(InternalError::UnrepresentableProperty {
+                key: properties[len - 1].key.to_string(),
+                format,
+                reason: "it conflicts with a preceding property".to_string(),
+            });
+        }
+    }
+    // only an empty document can fail without any property causing it
+    Err(InternalError::UnrepresentableProperty {
+        key: String::new(),
+        format,
+        reason,
+    })
+}
+
+#[cfg(test)]
+mod convert_tests {
+    use super::*;
+
+    fn converted(properties: &[(&str, &str)], format: Format) -> Result<String, InternalError> {
+        let properties = properties
+            .iter()
+            .map(|(key, value)| Property::new(*key, *value))
+            .collect();
+        let document = convert(properties, format)?;
+        let mut output: Vec<u8> = Vec::new();
+        document.write(&mut output, LineEnding::Lf).unwrap();
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    fn error_message(properties: &[(&str, &str)], format: Format) -> String {
+        converted(properties, format).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn should_write_properties_in_target_format() {
+        let properties = [("A", "1"), ("B", "x y"), ("C", "")];
+
+        assert_eq!(
+            converted(&properties, Format::Dotenv).unwrap(),
+            "A=1\nB=x y\nC=\n"
+        );
+        assert_eq!(
+            converted(&properties, Format::Json).unwrap(),
+            "{\n  \"A\": 1,\n  \"B\": \"x y\",\n  \"C\": \"\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn should_write_empty_documents() {
+        assert_eq!(converted(&[], Format::Json).unwrap(), "{}\n");
+        assert!(converted(&[], Format::Xml)
+            .unwrap()
+            .ends_with("<properties>\n</properties>\n"));
+        assert_eq!(converted(&[], Format::Yaml).unwrap(), "");
+        assert_eq!(converted(&[], Format::Properties).unwrap(), "");
+    }
+
+    #[test]
+    fn should_write_flat_json_objects() {
+        assert_eq!(
+            converted(
+                &[("server", "a"), ("server.port", "8080"), ("hosts[0]", "x")],
+                Format::Json
+            )
+            .unwrap(),
+            "{\n  \"server\": \"a\",\n  \"server.port\": 8080,\n  \"hosts[0]\": \"x\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn should_nest_dotted_keys() {
+        assert_eq!(
+            converted(
+                &[("server.port", "8080"), ("server.hosts[0]", "a")],
+                Format::Yaml
+            )
+            .unwrap(),
+            "server:\n  port: 8080\n  hosts:\n    - a\n"
+        );
+    }
+
+    #[test]
+    fn should_reject_keys_invalid_in_format() {
+        assert_eq!(
+            error_message(&[("A", "1"), ("my key", "x")], Format::Dotenv),
+            "property 'my key' cannot be represented in the dotenv format: the output cannot be read back (cannot parse property at line 1, column 4: expected '='\nmy key=x\n   ^)"
+        );
+    }
+
+    #[test]
+    fn should_reject_keys_conflicting_with_values() {
+        assert_eq!(
+            error_message(&[("server", "a"), ("server.port", "1")], Format::Yaml),
+            "property 'server.port' cannot be represented in the yaml format: it is nested under the value of 'server'"
+        );
+        assert_eq!(
+            error_message(&[("a.b[0]", "x"), ("a.b", "y")], Format::Toml),
+            "property 'a.b' cannot be represented in the toml format: it has a value and nested properties"
+        );
+    }
+}
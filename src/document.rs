@@ -0,0 +1,110 @@
+use crate::dotenv_document::DotenvDocument;
+use crate::hocon_document::HoconDocument;
+use crate::ini_document::IniDocument;
+use crate::json_document::JsonDocument;
+use crate::model::{DuplicatePolicy, Format, InternalError, LineEnding, Property};
+use crate::properties_document::PropertiesDocument;
+use crate::toml_document::TomlDocument;
+use crate::xml_document::XmlDocument;
+use crate::yaml_document::YamlDocument;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::Write;
+
+/// A configuration document whose values are addressed through flat property keys, such as
+/// 'server.port' or 'servers[0].host', and which is written back retaining the formatting of
+/// its source.
+pub trait Document<'a> {
+    /// The properties defined in the document, with the index used to update them.
+    fn properties(&self) -> Vec<(usize, Property<'_>)>;
+
+    /// Replaces the value of the property at `index`, leaving the rest of the document untouched.
+    /// Fails with `InternalError::UnrepresentableProperty` when the format cannot hold `value`.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError>;
+
+    /// Adds a new property to the document. Fails with `InternalError::UnrepresentableProperty`
+    /// when the format cannot hold it.
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError>;
+
+    /// Writes the document terminating every line with `line_ending`.
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()>;
+
+    /// Applies `policy` to the properties defined more than once. Formats which cannot define a
+    /// key more than once ignore it.
+    fn resolve_duplicates(&mut self, _policy: DuplicatePolicy) -> Result<(), InternalError> {
+        Ok(())
+    }
+
+    /// Properties whose value is an expression resolved when the document is loaded, such as a
+    /// substitution, and which are therefore exposed with its source text. Formats without such
+    /// expressions have none.
+    fn unresolved_properties(&self) -> Vec<(usize, Property<'_>)> {
+        Vec::new()
+    }
+
+    /// Properties commented out in the document, with the index used to activate them. Formats
+    /// without a notion of commented out properties have none.
+    fn commented_properties(&self) -> Vec<(usize, Property<'_>)> {
+        Vec::new()
+    }
+
+    /// Replaces the commented out property at `index` with `property`. Only called with indexes
+    /// returned by `commented_properties`.
+    fn activate(&mut self, _index: usize, _property: Property<'a>) -> Result<(), InternalError> {
+        Ok(())
+    }
+}
+
+/// Applies `policy` to the definitions of line-based documents, given as their index, key and
+/// line number in document order, returning the indexes of the definitions to remove.
+pub fn removed_duplicates<'k>(
+    definitions: impl IntoIterator<Item = (usize, &'k str, i32)>,
+    policy: DuplicatePolicy,
+) -> Result<HashSet<usize>, InternalError> {
+    let mut removed: HashSet<usize> = HashSet::new();
+    if policy == DuplicatePolicy::KeepAll {
+        return Ok(removed);
+    }
+    let mut retained: HashMap<&str, (usize, i32)> = HashMap::new();
+    for (index, key, line_num) in definitions {
+        match retained.get(key) {
+            None => {
+                retained.insert(key, (index, line_num));
+            }
+            Some(&(previous, previous_line_num)) => match policy {
+                DuplicatePolicy::Error => {
+                    return Err(InternalError::DuplicateKey {
+                        key: key.to_string(),
+                        first_line_num: previous_line_num,
+                        second_line_num: line_num,
+                    })
+                }
+                DuplicatePolicy::FirstWins => {
+                    removed.insert(index);
+                }
+                _ => {
+                    removed.insert(previous);
+                    retained.insert(key, (index, line_num));
+                }
+            },
+        }
+    }
+    Ok(removed)
+}
+
+/// Parses `text` as a document of the given format.
+pub fn parse_document(
+    text: &str,
+    format: Format,
+) -> Result<Box<dyn Document<'_> + '_>, InternalError> {
+    Ok(match format {
+        Format::Properties => Box::new(PropertiesDocument::parse(text)?),
+        Format::Yaml => Box::new(YamlDocument::parse(text)?),
+        Format::Toml => Box::new(TomlDocument::parse(text)?),
+        Format::Json => Box::new(JsonDocument::parse(text)?),
+        Format::Dotenv => Box::new(DotenvDocument::parse(text)?),
+        Format::Xml => Box::new(XmlDocument::parse(text)?),
+        Format::Ini => Box::new(IniDocument::parse(text)?),
+        Format::Hocon => Box::new(HoconDocument::parse(text)?),
+    })
+}
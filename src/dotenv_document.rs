@@ -0,0 +1,588 @@
+use crate::document::{removed_duplicates, Document};
+use crate::line_ending::split_physical_lines;
+use crate::model::{DuplicatePolicy, InternalError, LineEnding, Property};
+use std::borrow::Cow;
+use std::io;
+use std::io::Write;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Quoting {
+    Unquoted,
+    Single,
+    Double,
+}
+
+/// A 'KEY=value' line, with the location of its value in the text of the entry.
+#[derive(Debug, PartialEq)]
+struct Assignment<'a> {
+    property: Property<'a>,
+    /// Byte offset where the value starts, opening quote included
+    value_start: usize,
+    /// Byte offset where the value ends, closing quote included
+    value_end: usize,
+    quoting: Quoting,
+    export: bool,
+    has_separator: bool,
+}
+
+/// A line of a dotenv document, or the lines of an assignment whose quoted value spans several
+/// lines, retaining its exact original text.
+#[derive(Debug, PartialEq)]
+struct Entry<'a> {
+    line_num: i32,
+    text: Cow<'a, str>,
+    assignment: Option<Assignment<'a>>,
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_key_char(c: char) -> bool {
+    !(c.is_whitespace() || c == '=' || c == '#' || c == '"' || c == '\'')
+}
+
+/// Decodes the escape sequences of a double-quoted value, '\n', '\r', '\t', '\\', '\"' and '\$'.
+/// Other backslashes are retained.
+fn unescape(raw: &str) -> Cow<'_, str> {
+    if !raw.contains(['\\', '\r']) {
+        return Cow::Borrowed(raw);
+    }
+    let mut value = String::with_capacity(raw.len());
+    let mut chars = raw.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(&escaped @ ('n' | 'r' | 't' | '\\' | '"' | '$'))) => {
+                chars.next();
+                value.push(match escaped {
+                    'n' => '\n',
+                    'r' => '\r',
+                    't' => '\t',
+                    other => other,
+                });
+            }
+            // line breaks within quotes are read as '\n' regardless of the line ending
+            ('\r', Some('\n')) => {}
+            ('\r', _) => value.push('\n'),
+            (c, _) => value.push(c),
+        }
+    }
+    Cow::Owned(value)
+}
+
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '$' => escaped.push_str("\\$"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Renders `value` with the given quoting, switching to double quotes when it cannot be
+/// represented otherwise. Values containing '$' are single-quoted so that docker-compose does not
+/// interpolate them, or double-quoted with '\$' when they cannot be.
+fn render_value(value: &str, quoting: Quoting) -> String {
+    let needs_quotes = value != value.trim_matches(is_blank)
+        || value.contains(['\n', '\r'])
+        || value.starts_with(['"', '\''])
+        || value.contains(" #")
+        || value.contains("\t#");
+    let interpolated = value.contains('$');
+    let single_quotable = !value.contains(['\'', '\n', '\r']);
+    match quoting {
+        Quoting::Unquoted if !needs_quotes && !interpolated => value.to_string(),
+        Quoting::Single if single_quotable => format!("'{}'", value),
+        _ if interpolated && single_quotable => format!("'{}'", value),
+        _ => format!("\"{}\"", escape(value)),
+    }
+}
+
+/// Parses the assignment at the start of `text`, which may continue on the following lines when
+/// its value is quoted. Returns the assignment with the length of its text, `None` for blank and
+/// comment lines, or the offset and description of the error.
+fn parse_assignment(text: &str) -> Result<Option<(Assignment<'_>, usize)>, (usize, &'static str)> {
+    let line_end = text.find(['\n', '\r']).unwrap_or(text.len());
+    let line = &text[..line_end];
+    let content = line.trim_start_matches(is_blank);
+    if content.is_empty() || content.starts_with('#') {
+        return Ok(None);
+    }
+    let mut position = line.len() - content.len();
+    let mut export = false;
+    if let Some(exported) = content.strip_prefix("export") {
+        let key = exported.trim_start_matches(is_blank);
+        if key.len() < exported.len() && key.starts_with(is_key_char) {
+            export = true;
+            position = line.len() - key.len();
+        }
+    }
+    let key_len = line[position..]
+        .find(|c: char| !is_key_char(c))
+        .unwrap_or(line.len() - position);
+    if key_len == 0 {
+        return Err((position, "expected a key"));
+    }
+    let key = &line[position..position + key_len];
+    let key_end = position + key_len;
+    position = line.len() - line[key_end..].trim_start_matches(is_blank).len();
+    if position == line.len() {
+        let assignment = Assignment {
+            property: Property {
+                key: Cow::Borrowed(key),
+                value: Cow::Borrowed(""),
+            },
+            value_start: key_end,
+            value_end: line.len(),
+            quoting: Quoting::Unquoted,
+            export,
+            has_separator: false,
+        };
+        return Ok(Some((assignment, line.len())));
+    }
+    if !line[position..].starts_with('=') {
+        return Err((position, "expected '='"));
+    }
+    let value_start = line.len() - line[position + 1..].trim_start_matches(is_blank).len();
+    let (value, quoting, value_end) = match text[value_start..].chars().next() {
+        Some('"') => {
+            let mut escaped = false;
+            let close = text[value_start + 1..].find(|c: char| {
+                let closing = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                closing
+            });
+            let Some(close) = close.map(|close| value_start + 1 + close) else {
+                return Err((value_start, "unterminated double-quoted value"));
+            };
+            (
+                unescape(&text[value_start + 1..close]),
+                Quoting::Double,
+                close + 1,
+            )
+        }
+        Some('\'') => {
+            let Some(close) = text[value_start + 1..]
+                .find('\'')
+                .map(|close| value_start + 1 + close)
+            else {
+                return Err((value_start, "unterminated single-quoted value"));
+            };
+            (
+                Cow::Borrowed(&text[value_start + 1..close]),
+                Quoting::Single,
+                close + 1,
+            )
+        }
+        _ => {
+            let raw = &line[value_start..];
+            let comment = raw
+                .match_indices('#')
+                .find(|(i, _)| {
+                    raw[..*i].ends_with(is_blank) || (*i == 0 && value_start > position + 1)
+                })
+                .map(|(i, _)| i)
+                .unwrap_or(raw.len());
+            let value = raw[..comment].trim_end_matches(is_blank);
+            (
+                Cow::Borrowed(value),
+                Quoting::Unquoted,
+                value_start + value.len(),
+            )
+        }
+    };
+    let entry_end = text[value_end..]
+        .find(['\n', '\r'])
+        .map(|i| value_end + i)
+        .unwrap_or(text.len());
+    let trailing = text[value_end..entry_end].trim_start_matches(is_blank);
+    let comment = trailing.starts_with('#') && trailing.len() < entry_end - value_end;
+    if !(trailing.is_empty() || comment) {
+        return Err((
+            entry_end - trailing.len(),
+            "unexpected characters after the value",
+        ));
+    }
+    let assignment = Assignment {
+        property: Property {
+            key: Cow::Borrowed(key),
+            value,
+        },
+        value_start,
+        value_end,
+        quoting,
+        export,
+        has_separator: true,
+    };
+    Ok(Some((assignment, entry_end)))
+}
+
+fn assignment_entry<'a>(line_num: i32, prefix: &str, property: Property<'a>) -> Entry<'a> {
+    let value = render_value(&property.value, Quoting::Unquoted);
+    let value_start = prefix.len() + property.key.len() + 1;
+    let text = format!("{}{}={}", prefix, property.key, value);
+    Entry {
+        line_num,
+        text: Cow::Owned(text),
+        assignment: Some(Assignment {
+            property,
+            value_start,
+            value_end: value_start + value.len(),
+            quoting: Quoting::Unquoted,
+            export: prefix.ends_with("export "),
+            has_separator: true,
+        }),
+    }
+}
+
+/// A dotenv file, as read by docker-compose: 'KEY=value' lines optionally prefixed by 'export',
+/// with unquoted, single-quoted or double-quoted values, the latter supporting escape sequences
+/// such as '\n'. Quoted values can span several lines. Every line is kept verbatim and only the
+/// values of the updated properties are rewritten, retaining their quoting when possible.
+#[derive(Debug, PartialEq, Default)]
+pub struct DotenvDocument<'a> {
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> DotenvDocument<'a> {
+    /// Parses all the lines of `text`, reporting every malformed line in a single
+    /// `InternalError::ParseErrors`.
+    pub fn parse(text: &'a str) -> Result<DotenvDocument<'a>, InternalError> {
+        let mut entries: Vec<Entry<'a>> = Vec::new();
+        let mut errors: Vec<InternalError> = Vec::new();
+        let mut position: usize = 0;
+        let mut line_num: i32 = 1;
+        while position < text.len() {
+            let rest = &text[position..];
+            let (assignment, len) = match parse_assignment(rest) {
+                Ok(Some((assignment, len))) => (Some(assignment), len),
+                Ok(None) => (None, rest.find(['\n', '\r']).unwrap_or(rest.len())),
+                Err((offset, message)) => {
+                    errors.push(InternalError::parse_error_at_offset(
+                        text,
+                        position + offset,
+                        message,
+                    ));
+                    (None, rest.find(['\n', '\r']).unwrap_or(rest.len()))
+                }
+            };
+            let entry_text = &rest[..len];
+            entries.push(Entry {
+                line_num,
+                text: Cow::Borrowed(entry_text),
+                assignment,
+            });
+            line_num += split_physical_lines(entry_text).len() as i32;
+            position += len
+                + if rest[len..].starts_with("\r\n") {
+                    2
+                } else {
+                    1
+                };
+        }
+        if !errors.is_empty() {
+            return Err(InternalError::ParseErrors(errors));
+        }
+        Ok(DotenvDocument { entries })
+    }
+}
+
+impl<'a> Document<'a> for DotenvDocument<'a> {
+    fn properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let assignment = entry.assignment.as_ref()?;
+                Some((index, assignment.property.clone()))
+            })
+            .collect()
+    }
+
+    /// Applies `policy` to the keys assigned more than once, removing the lines of the
+    /// assignments which are not retained.
+    fn resolve_duplicates(&mut self, policy: DuplicatePolicy) -> Result<(), InternalError> {
+        let definitions = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let assignment = entry.assignment.as_ref()?;
+                Some((index, assignment.property.key.as_ref(), entry.line_num))
+            });
+        let removed = removed_duplicates(definitions, policy)?;
+        let mut index: usize = 0;
+        self.entries.retain(|_| {
+            let keep = !removed.contains(&index);
+            index += 1;
+            keep
+        });
+        Ok(())
+    }
+
+    /// Replaces the value at `index` with the same quoting, switching to double quotes when the
+    /// value requires them. Inline comments following the value are retained.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError> {
+        let entry = &mut self.entries[index];
+        if let Some(assignment) = &mut entry.assignment {
+            let mut rendered = render_value(value, assignment.quoting);
+            if !assignment.has_separator {
+                rendered.insert(0, '=');
+                assignment.has_separator = true;
+            }
+            let text = entry.text.to_mut();
+            text.replace_range(assignment.value_start..assignment.value_end, &rendered);
+            assignment.value_end = assignment.value_start + rendered.len();
+            assignment.property.value = Cow::Owned(value.to_string());
+        }
+        Ok(())
+    }
+
+    /// Assignments commented out in comment lines such as '# KEY=value', with the index of their
+    /// entry.
+    fn commented_properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                if entry.assignment.is_some() {
+                    return None;
+                }
+                let uncommented = entry
+                    .text
+                    .trim_start_matches(is_blank)
+                    .strip_prefix('#')?
+                    .trim_start_matches(is_blank);
+                match parse_assignment(uncommented) {
+                    Ok(Some((assignment, _))) if assignment.has_separator => {
+                        Some((index, assignment.property))
+                    }
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Replaces the comment line at `index` with `property`, retaining the indentation and the
+    /// 'export' prefix of the commented out assignment.
+    fn activate(&mut self, index: usize, property: Property<'a>) -> Result<(), InternalError> {
+        let entry = &self.entries[index];
+        let indentation_len = entry.text.len() - entry.text.trim_start_matches(is_blank).len();
+        let uncommented = entry.text[indentation_len..]
+            .trim_start_matches('#')
+            .trim_start_matches(is_blank);
+        let export = uncommented
+            .strip_prefix("export")
+            .is_some_and(|rest| rest.starts_with(is_blank));
+        let prefix = format!(
+            "{}{}",
+            &entry.text[..indentation_len],
+            if export { "export " } else { "" }
+        );
+        self.entries[index] = assignment_entry(entry.line_num, &prefix, property);
+        Ok(())
+    }
+
+    /// Appends a new 'KEY=value' line at the end of the document, prefixed by 'export' when all
+    /// the existing assignments are.
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError> {
+        let mut assignments = self.entries.iter().filter_map(|e| e.assignment.as_ref());
+        let export = assignments.next().is_some_and(|first| first.export)
+            && assignments.all(|assignment| assignment.export);
+        let line_num = self
+            .entries
+            .last()
+            .map(|entry| entry.line_num + split_physical_lines(&entry.text).len() as i32)
+            .unwrap_or(1);
+        let prefix = if export { "export " } else { "" };
+        self.entries
+            .push(assignment_entry(line_num, prefix, property));
+        Ok(())
+    }
+
+    /// Writes the document terminating every physical line with `line_ending`.
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()> {
+        for entry in &self.entries {
+            for physical_line in split_physical_lines(&entry.text) {
+                output.write_all(physical_line.as_bytes())?;
+                output.write_all(line_ending.as_str().as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod dotenv_document_tests {
+    use super::*;
+
+    fn parse(s: &str) -> DotenvDocument<'_> {
+        DotenvDocument::parse(s).unwrap()
+    }
+
+    fn properties(document: &DotenvDocument) -> Vec<(String, String)> {
+        Document::properties(document)
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect()
+    }
+
+    fn properties_of(properties: Vec<(usize, Property)>) -> Vec<(String, String)> {
+        properties
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect()
+    }
+
+    fn write(document: &DotenvDocument) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        document.write(&mut output, LineEnding::Lf).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_should_read_quoting_export_and_comments() {
+        let document = parse(
+            "# comment\nexport A=1\nB = b c # inline\nC='x # y'\nD=\"l1\\nl2 \\\"q\\\" \\$x\"\nE=\"multi\r\nline\" # c\nF=\nG\nH=a#b\n",
+        );
+
+        assert_eq!(
+            properties(&document),
+            pairs(&[
+                ("A", "1"),
+                ("B", "b c"),
+                ("C", "x # y"),
+                ("D", "l1\nl2 \"q\" $x"),
+                ("E", "multi\nline"),
+                ("F", ""),
+                ("G", ""),
+                ("H", "a#b"),
+            ])
+        );
+        assert_eq!(document.entries[6].line_num, 8);
+    }
+
+    #[test]
+    fn parse_should_report_all_errors() {
+        match DotenvDocument::parse("A=\"x\nB='y'z\n=1\nC D\n") {
+            Err(InternalError::ParseErrors(errors)) => assert_eq!(
+                errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+                vec![
+                    "cannot parse property at line 1, column 3: unterminated double-quoted value\nA=\"x\n  ^",
+                    "cannot parse property at line 2, column 6: unexpected characters after the value\nB='y'z\n     ^",
+                    "cannot parse property at line 3, column 1: expected a key\n=1\n^",
+                    "cannot parse property at line 4, column 3: expected '='\nC D\n  ^",
+                ]
+            ),
+            _ => panic!("result is not ParseErrors"),
+        }
+    }
+
+    #[test]
+    fn write_should_reproduce_input_exactly() {
+        let input = "# c\n  export  A = 1 # x\n\nB='2'\nC=\"3\n4\"\n";
+
+        assert_eq!(write(&parse(input)), input);
+    }
+
+    #[test]
+    fn set_value_should_keep_quoting_and_inline_comments() {
+        let mut document = parse("A=1 # port\nB='x'\nC=\"y\"\nD=z\nE='w'\nF\n");
+
+        document.set_value(0, "2").unwrap();
+        document.set_value(1, "a b").unwrap();
+        document.set_value(2, "line\n\"q\"").unwrap();
+        document.set_value(3, " padded").unwrap();
+        document.set_value(4, "it's").unwrap();
+        document.set_value(5, "f").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "A=2 # port\nB='a b'\nC=\"line\\n\\\"q\\\"\"\nD=\" padded\"\nE=\"it's\"\nF=f\n"
+        );
+        assert_eq!(
+            properties(&document)[2],
+            ("C".to_string(), "line\n\"q\"".to_string())
+        );
+    }
+
+    #[test]
+    fn set_value_should_prevent_interpolation_of_dollar_signs() {
+        let mut document = parse("A=1\nB=\"x\"\nC='y'\nD=z\n");
+
+        document.set_value(0, "pa$$word").unwrap();
+        document.set_value(1, "${HOME}").unwrap();
+        document.set_value(2, "$1").unwrap();
+        document.set_value(3, "it's $5").unwrap();
+        document.push(Property::new("E", "$E")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "A='pa$$word'\nB='${HOME}'\nC='$1'\nD=\"it's \\$5\"\nE='$E'\n"
+        );
+    }
+
+    #[test]
+    fn write_should_read_back_dollar_signs_of_double_quoted_values() {
+        let mut document = parse("A=1\n");
+
+        document.set_value(0, "it's $5 \\$").unwrap();
+        let output = write(&document);
+
+        assert_eq!(output, "A=\"it's \\$5 \\\\\\$\"\n");
+        assert_eq!(properties(&parse(&output)), pairs(&[("A", "it's $5 \\$")]));
+    }
+
+    #[test]
+    fn resolve_duplicates_should_remove_discarded_assignments() {
+        let mut document = parse("A=1\nB=2\nA=3\n");
+
+        document
+            .resolve_duplicates(DuplicatePolicy::LastWins)
+            .unwrap();
+
+        assert_eq!(write(&document), "B=2\nA=3\n");
+    }
+
+    #[test]
+    fn activate_should_replace_commented_assignments() {
+        let mut document = parse("  # export A=1\n# B = x\n# not an assignment\n");
+
+        let commented = properties_of(document.commented_properties());
+        document.activate(0, Property::new("A", "2")).unwrap();
+
+        assert_eq!(commented, pairs(&[("A", "1"), ("B", "x")]));
+        assert_eq!(
+            write(&document),
+            "  export A=2\n# B = x\n# not an assignment\n"
+        );
+    }
+
+    #[test]
+    fn push_should_follow_export_prefix_of_document() {
+        let mut exported = parse("export A=1\nexport B=2\n");
+        let mut mixed = parse("export A=1\nB=2\n");
+
+        exported.push(Property::new("C", "a # b")).unwrap();
+        mixed.push(Property::new("C", "3")).unwrap();
+
+        assert_eq!(
+            write(&exported),
+            "export A=1\nexport B=2\nexport C=\"a # b\"\n"
+        );
+        assert_eq!(write(&mixed), "export A=1\nB=2\nC=3\n");
+    }
+}
@@ -0,0 +1,212 @@
+use crate::model::{Encoding, Format, InternalError};
+use crate::xml_document::declare_encoding;
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::fmt::Write;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+impl Encoding {
+    /// The name of the encoding in XML declarations.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Iso88591 => "ISO-8859-1",
+        }
+    }
+}
+
+/// Decodes `bytes` with the given encoding. Input starting with a UTF-8 byte order mark is
+/// always decoded as UTF-8 and the mark is stripped.
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<String, InternalError> {
+    if let Some(without_bom) = bytes.strip_prefix(UTF8_BOM) {
+        return decode_utf8(without_bom);
+    }
+    match encoding {
+        Encoding::Utf8 => decode_utf8(bytes),
+        Encoding::Iso88591 => Ok(bytes.iter().map(|b| *b as char).collect()),
+    }
+}
+
+fn decode_utf8(bytes: &[u8]) -> Result<String, InternalError> {
+    String::from_utf8(bytes.to_vec()).map_err(|err| {
+        InternalError::EncodingError(format!(
+            "input is not valid UTF-8 at byte {}, consider using '--input-encoding iso-8859-1'",
+            err.utf8_error().valid_up_to()
+        ))
+    })
+}
+
+/// Writes `c` in the escaped form of `format` which only uses ISO-8859-1 characters, or returns
+/// `None` if the format has none valid everywhere the character may appear.
+fn escape_non_latin1(c: char, format: Format) -> Option<String> {
+    match format {
+        // JSON can only contain such characters in strings, where '\uxxxx' escapes are valid
+        Format::Properties | Format::Json => {
+            let mut escaped = String::new();
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                write!(escaped, "\\u{:04X}", unit).unwrap();
+            }
+            Some(escaped)
+        }
+        // entry keys and values are text and attributes, where character references are valid
+        Format::Xml => Some(format!("&#x{:X};", c as u32)),
+        _ => None,
+    }
+}
+
+/// Encodes `text`, written in `format`, with the given encoding. Characters which cannot be
+/// represented in ISO-8859-1 are escaped in the syntax of the format: '\uxxxx' escapes in
+/// properties and JSON, using surrogate pairs outside the basic multilingual plane, and character
+/// references in XML, whose declaration is updated to name the encoding. Fails with
+/// `InternalError::EncodingError` for the other formats.
+pub fn encode(text: &str, encoding: Encoding, format: Format) -> Result<Vec<u8>, InternalError> {
+    let text = if format == Format::Xml {
+        declare_encoding(text, encoding)
+    } else {
+        Cow::Borrowed(text)
+    };
+    match encoding {
+        Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        Encoding::Iso88591 => {
+            let mut bytes: Vec<u8> = Vec::with_capacity(text.len());
+            for c in text.chars() {
+                if (c as u32) <= 0xFF {
+                    bytes.push(c as u8);
+                    continue;
+                }
+                let Some(escaped) = escape_non_latin1(c, format) else {
+                    return Err(InternalError::EncodingError(format!(
+                        "character '{}' cannot be written in ISO-8859-1 in the {} format, consider using '--output-encoding utf-8'",
+                        c,
+                        format.to_possible_value().unwrap().get_name()
+                    )));
+                };
+                bytes.extend_from_slice(escaped.as_bytes());
+            }
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod decode_tests {
+    use super::*;
+
+    #[test]
+    fn should_decode_utf8() {
+        let result = decode("kéy=välue".as_bytes(), Encoding::Utf8);
+
+        assert_eq!(result.unwrap(), "kéy=välue");
+    }
+
+    #[test]
+    fn should_fail_on_invalid_utf8() {
+        let result = decode(&[b'a', b'=', 0xE9], Encoding::Utf8);
+
+        match result {
+            Err(InternalError::EncodingError(message)) => assert_eq!(
+                message,
+                "input is not valid UTF-8 at byte 2, consider using '--input-encoding iso-8859-1'"
+            ),
+            _ => panic!("result is not EncodingError"),
+        }
+    }
+
+    #[test]
+    fn should_decode_iso_8859_1() {
+        let result = decode(&[b'a', b'=', 0xE9, 0xFF], Encoding::Iso88591);
+
+        assert_eq!(result.unwrap(), "a=\u{e9}\u{ff}");
+    }
+
+    #[test]
+    fn should_strip_utf8_bom() {
+        let result = decode(&[0xEF, 0xBB, 0xBF, b'a', b'=', b'b'], Encoding::Utf8);
+
+        assert_eq!(result.unwrap(), "a=b");
+    }
+
+    #[test]
+    fn should_decode_as_utf8_when_bom_present() {
+        let mut bytes = vec![0xEF, 0xBB, 0xBF];
+        bytes.extend_from_slice("a=é".as_bytes());
+
+        let result = decode(&bytes, Encoding::Iso88591);
+
+        assert_eq!(result.unwrap(), "a=é");
+    }
+}
+
+#[cfg(test)]
+mod encode_tests {
+    use super::*;
+
+    #[test]
+    fn should_encode_utf8() {
+        assert_eq!(
+            encode("a=é€", Encoding::Utf8, Format::Properties).unwrap(),
+            "a=é€".as_bytes()
+        );
+    }
+
+    #[test]
+    fn should_encode_latin1_characters_as_single_bytes() {
+        assert_eq!(
+            encode("a=é", Encoding::Iso88591, Format::Properties).unwrap(),
+            vec![b'a', b'=', 0xE9]
+        );
+    }
+
+    #[test]
+    fn should_escape_characters_not_representable_in_latin1() {
+        assert_eq!(
+            encode("a=€", Encoding::Iso88591, Format::Properties).unwrap(),
+            b"a=\\u20AC".to_vec()
+        );
+    }
+
+    #[test]
+    fn should_escape_supplementary_characters_as_surrogate_pairs() {
+        assert_eq!(
+            encode("\u{1f600}", Encoding::Iso88591, Format::Properties).unwrap(),
+            b"\\uD83D\\uDE00".to_vec()
+        );
+    }
+
+    #[test]
+    fn should_escape_characters_in_json_strings() {
+        assert_eq!(
+            encode("{\"a\": \"€\"}", Encoding::Iso88591, Format::Json).unwrap(),
+            b"{\"a\": \"\\u20AC\"}".to_vec()
+        );
+    }
+
+    #[test]
+    fn should_write_character_references_in_xml() {
+        assert_eq!(
+            encode(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<properties>\n<entry key=\"€\">é😀</entry>\n</properties>\n",
+                Encoding::Iso88591,
+                Format::Xml
+            )
+            .unwrap(),
+            b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<properties>\n<entry key=\"&#x20AC;\">\xE9&#x1F600;</entry>\n</properties>\n".to_vec()
+        );
+    }
+
+    #[test]
+    fn should_fail_on_characters_not_representable_in_format() {
+        match encode("a: €", Encoding::Iso88591, Format::Yaml) {
+            Err(InternalError::EncodingError(message)) => assert_eq!(
+                message,
+                "character '€' cannot be written in ISO-8859-1 in the yaml format, consider using '--output-encoding utf-8'"
+            ),
+            _ => panic!("result is not EncodingError"),
+        }
+        assert_eq!(
+            encode("a: é", Encoding::Iso88591, Format::Yaml).unwrap(),
+            vec![b'a', b':', b' ', 0xE9]
+        );
+    }
+}
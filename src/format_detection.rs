@@ -0,0 +1,177 @@
+use crate::document::parse_document;
+use crate::model::Format;
+use std::path::Path;
+
+/// Detects the format of a file from its name: '.env' files and the extensions '.properties',
+/// '.yaml', '.yml', '.toml', '.json', '.env', '.xml', '.ini' and '.hocon'. Returns `None` for other
+/// names, including '.conf' which is used by too many unrelated formats.
+pub fn detect_format_of_path(path: &str) -> Option<Format> {
+    let path = Path::new(path);
+    let name = path.file_name()?.to_str()?;
+    if name == ".env" || name.starts_with(".env.") {
+        return Some(Format::Dotenv);
+    }
+    match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+        "properties" => Some(Format::Properties),
+        "yaml" | "yml" => Some(Format::Yaml),
+        "toml" => Some(Format::Toml),
+        "json" => Some(Format::Json),
+        "env" => Some(Format::Dotenv),
+        "xml" => Some(Format::Xml),
+        "ini" => Some(Format::Ini),
+        "hocon" => Some(Format::Hocon),
+        _ => None,
+    }
+}
+
+fn is_significant(line: &str) -> bool {
+    let line = line.trim();
+    !(line.is_empty() || line.starts_with(['#', '!', ';']))
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// Whether a line such as 'server:' is followed by a more indented block, which is not valid in
+/// properties where the line would be the key 'server' with an empty value.
+fn has_yaml_block(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().filter(|line| is_significant(line)).collect();
+    lines.windows(2).any(|pair| {
+        let parent = pair[0].trim_end();
+        parent.ends_with(':') && !parent.contains('=') && indentation(pair[1]) > indentation(parent)
+    })
+}
+
+fn is_dotenv_assignment(line: &str) -> bool {
+    let assignment = line.strip_prefix("export ").unwrap_or(line).trim_start();
+    let key_len = assignment
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(assignment.len());
+    key_len > 0 && assignment[key_len..].starts_with('=')
+}
+
+/// Detects the format of `text` from its content, for inputs without a recognisable file name.
+/// The structured formats are only detected when syntax which properties files cannot contain
+/// is found and `text` can be parsed in the format, falling back to properties for ambiguous
+/// inputs such as 'key: value' lines.
+pub fn detect_format(text: &str) -> Format {
+    let parses = |format: Format| parse_document(text, format).is_ok();
+    let lines: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| is_significant(line))
+        .collect();
+    let Some(first) = lines.first() else {
+        return Format::Properties;
+    };
+    if first.starts_with('<') && parses(Format::Xml) {
+        return Format::Xml;
+    }
+    if first.starts_with(['{', '[']) && parses(Format::Json) {
+        return Format::Json;
+    }
+    if lines.iter().any(|line| line.starts_with('[')) {
+        if parses(Format::Toml) {
+            return Format::Toml;
+        }
+        if parses(Format::Ini) {
+            return Format::Ini;
+        }
+    }
+    let yaml = first.starts_with("---") || has_yaml_block(text);
+    if yaml && parses(Format::Yaml) {
+        return Format::Yaml;
+    }
+    let hocon = first.starts_with('{')
+        || lines
+            .iter()
+            .any(|line| line.ends_with('{') || line.starts_with("include "));
+    if hocon && parses(Format::Hocon) {
+        return Format::Hocon;
+    }
+    let dotenv = lines.iter().any(|line| line.starts_with("export "))
+        && lines.iter().all(|line| is_dotenv_assignment(line));
+    if dotenv && parses(Format::Dotenv) {
+        return Format::Dotenv;
+    }
+    Format::Properties
+}
+
+#[cfg(test)]
+mod detect_format_of_path_tests {
+    use super::*;
+
+    #[test]
+    fn should_detect_formats_from_extensions() {
+        assert_eq!(
+            detect_format_of_path("config/application.yml"),
+            Some(Format::Yaml)
+        );
+        assert_eq!(detect_format_of_path("a.JSON"), Some(Format::Json));
+        assert_eq!(detect_format_of_path("app.hocon"), Some(Format::Hocon));
+        assert_eq!(
+            detect_format_of_path("a.properties"),
+            Some(Format::Properties)
+        );
+    }
+
+    #[test]
+    fn should_detect_dotenv_file_names() {
+        assert_eq!(detect_format_of_path(".env"), Some(Format::Dotenv));
+        assert_eq!(
+            detect_format_of_path("dir/.env.local"),
+            Some(Format::Dotenv)
+        );
+        assert_eq!(detect_format_of_path("prod.env"), Some(Format::Dotenv));
+    }
+
+    #[test]
+    fn should_not_detect_unknown_names() {
+        assert_eq!(detect_format_of_path("config.txt"), None);
+        assert_eq!(detect_format_of_path("config"), None);
+        assert_eq!(detect_format_of_path("application.conf"), None);
+    }
+}
+
+#[cfg(test)]
+mod detect_format_tests {
+    use super::*;
+
+    #[test]
+    fn should_detect_structured_formats() {
+        assert_eq!(detect_format("{\"a\": 1}\n"), Format::Json);
+        assert_eq!(
+            detect_format("<?xml version=\"1.0\"?>\n<properties>\n</properties>\n"),
+            Format::Xml
+        );
+        assert_eq!(detect_format("# c\n[server]\nport = 8080\n"), Format::Toml);
+        assert_eq!(detect_format("[server]\nhost = a b\n"), Format::Ini);
+        assert_eq!(detect_format("server:\n  port: 8080\n"), Format::Yaml);
+        assert_eq!(detect_format("---\nname: x\n"), Format::Yaml);
+        assert_eq!(detect_format("server {\n  port = 8080\n}\n"), Format::Hocon);
+    }
+
+    #[test]
+    fn should_detect_dotenv_from_export() {
+        assert_eq!(detect_format("export A=1\nB=\"x y\"\n"), Format::Dotenv);
+    }
+
+    #[test]
+    fn should_default_to_properties() {
+        assert_eq!(detect_format(""), Format::Properties);
+        assert_eq!(detect_format("A=1\nb.c=x y\n"), Format::Properties);
+        assert_eq!(detect_format("url: jdbc:h2\nport=1\n"), Format::Properties);
+        assert_eq!(detect_format("A=1\nB=\"x y\"\n"), Format::Properties);
+    }
+
+    #[test]
+    fn should_treat_colon_separated_properties_as_properties() {
+        assert_eq!(
+            detect_format("log4j.rootLogger: INFO, stdout\nlog4j.appender.stdout: x\n"),
+            Format::Properties
+        );
+        assert_eq!(detect_format("name: x\nport: 1\n"), Format::Properties);
+        assert_eq!(detect_format("- a\nkey:\n"), Format::Properties);
+    }
+}
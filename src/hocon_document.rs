@@ -0,0 +1,942 @@
+use crate::document::Document;
+use crate::json_document::{inferred_value, is_json_number, quote};
+use crate::keys::{insert_node, key_segments, segments_key, Node, Nodes, Segment};
+use crate::line_ending::split_physical_lines;
+use crate::model::{InternalError, LineEnding, Property};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::Write;
+
+/// Indentation of nested objects added to documents which contain none.
+const DEFAULT_INDENT_STEP: &str = "  ";
+
+/// Characters which cannot appear in unquoted strings and keys.
+const FORBIDDEN: &str = "$\"{}[]:=,+#`^?!@*&\\";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ScalarKind {
+    Quoted,
+    Unquoted,
+    Number,
+    Boolean,
+    Null,
+    /// Concatenations such as '"a" b', exposed with their source text
+    Verbatim,
+    /// Values containing substitutions such as '${HOME}/app', exposed with their source text
+    Substitution,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ContainerKind {
+    Object,
+    Array,
+}
+
+/// A scalar of the document with the flattened key it is reachable with, such as 'a.b[1].c'.
+#[derive(Debug)]
+struct Scalar {
+    key: String,
+    value: String,
+    start: usize,
+    end: usize,
+    kind: ScalarKind,
+}
+
+/// An object or an array of the document, where added properties are inserted.
+#[derive(Debug)]
+struct Container {
+    kind: ContainerKind,
+    /// Byte offset of the opening bracket, absent for the root object without braces
+    open: Option<usize>,
+    /// Byte offset of the closing bracket, or the end of the text for the root object without
+    /// braces
+    close: usize,
+    /// Byte offset where the last member ends, if the container is not empty
+    last_member_end: Option<usize>,
+    /// Whether members are on lines of their own
+    multiline: bool,
+    /// Indentation of the members, if the container is not empty
+    member_indent: Option<String>,
+    /// Indentation of the line where the container starts
+    indent: String,
+}
+
+/// The kind of container addressed by `segment`.
+fn container_kind(segment: &Segment) -> ContainerKind {
+    match segment {
+        Segment::Key(_) => ContainerKind::Object,
+        Segment::Index(_) => ContainerKind::Array,
+    }
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    !(c.is_whitespace() || FORBIDDEN.contains(c))
+}
+
+/// Whether `text` can be written as an unquoted string, read back as the same string.
+fn is_unquoted_safe(text: &str) -> bool {
+    !text.is_empty()
+        && text.chars().all(is_unquoted_char)
+        && !text.contains("//")
+        && !["true", "false", "null"].contains(&text)
+        && !is_json_number(text, false)
+}
+
+fn render_key(key: &str) -> String {
+    if !key.is_empty()
+        && key.chars().all(|c| is_unquoted_char(c) && c != '.')
+        && !key.contains("//")
+    {
+        key.to_string()
+    } else {
+        quote(key)
+    }
+}
+
+/// Renders `text` as a value of the same kind as the value it replaces when it represents one,
+/// otherwise as a string.
+fn typed_value(text: &str, kind: ScalarKind) -> String {
+    match kind {
+        ScalarKind::Number if is_json_number(text, false) => text.to_string(),
+        ScalarKind::Boolean if text == "true" || text == "false" => text.to_string(),
+        ScalarKind::Unquoted if is_unquoted_safe(text) => text.to_string(),
+        ScalarKind::Null | ScalarKind::Verbatim | ScalarKind::Substitution => inferred_value(text),
+        _ => quote(text),
+    }
+}
+
+/// Formatting of the content added to the document, detected from the existing content.
+struct Layout {
+    multiline: bool,
+    step: String,
+}
+
+impl Layout {
+    /// Renders an object member, collapsing chains of single keys into a path expression such as
+    /// 'a.b.c = 1'.
+    fn render_member(&self, path: String, node: &Node, indent: &str) -> String {
+        match node {
+            Node::Value(value) => format!("{} = {}", path, inferred_value(value)),
+            Node::Children(children) => match children.as_slice() {
+                [(Segment::Key(key), child)] => {
+                    self.render_member(format!("{}.{}", path, render_key(key)), child, indent)
+                }
+                [(Segment::Key(_), _), ..] => {
+                    format!("{} {}", path, self.render_element(node, indent))
+                }
+                _ => format!("{} = {}", path, self.render_element(node, indent)),
+            },
+        }
+    }
+
+    /// Renders a value, or the object or array made of `node`.
+    fn render_element(&self, node: &Node, indent: &str) -> String {
+        let children = match node {
+            Node::Value(value) => return inferred_value(value),
+            Node::Children(children) => children,
+        };
+        let (open, close) = match children.first() {
+            Some((Segment::Index(_), _)) => ("[", "]"),
+            _ => ("{", "}"),
+        };
+        let member_indent = format!("{}{}", indent, self.step);
+        let members = self.render_children(children, &member_indent);
+        if self.multiline {
+            format!(
+                "{}\n{}{}\n{}{}",
+                open,
+                member_indent,
+                members.join(format!("\n{}", member_indent).as_str()),
+                indent,
+                close
+            )
+        } else {
+            format!("{} {} {}", open, members.join(", "), close)
+        }
+    }
+
+    /// Renders the members of an object or the elements of an array at the given indentation.
+    fn render_children(&self, children: &Nodes, indent: &str) -> Vec<String> {
+        children
+            .iter()
+            .map(|(segment, node)| match segment {
+                Segment::Key(key) => self.render_member(render_key(key), node, indent),
+                Segment::Index(_) => self.render_element(node, indent),
+            })
+            .collect()
+    }
+}
+
+struct HoconParser<'t> {
+    text: &'t str,
+    position: usize,
+    scalars: Vec<Scalar>,
+    containers: HashMap<String, Container>,
+    step: Option<String>,
+    /// Whether the values being parsed are addressable, which they are not when appended with
+    /// '+='
+    recording: bool,
+}
+
+impl<'t> HoconParser<'t> {
+    fn error(&self, message: &str) -> InternalError {
+        InternalError::parse_error_at_offset(self.text, self.position, message)
+    }
+
+    fn rest(&self) -> &'t str {
+        &self.text[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    /// Skips whitespace and comments, including line breaks if `newlines`.
+    fn skip_blank(&mut self, newlines: bool) {
+        loop {
+            let rest = self.rest();
+            let trimmed = if newlines {
+                rest.trim_start()
+            } else {
+                rest.trim_start_matches(|c: char| c.is_whitespace() && c != '\n' && c != '\r')
+            };
+            self.position += rest.len() - trimmed.len();
+            if trimmed.starts_with('#') || trimmed.starts_with("//") {
+                self.position += trimmed.find(['\n', '\r']).unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn line_indent(&self, offset: usize) -> String {
+        let line_start = self.text[..offset]
+            .rfind(['\n', '\r'])
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let line = &self.text[line_start..offset];
+        line[..line.len() - line.trim_start_matches([' ', '\t']).len()].to_string()
+    }
+
+    fn parse_quoted(&mut self) -> Result<String, InternalError> {
+        if let Some(rest) = self.rest().strip_prefix("\"\"\"") {
+            let Some(len) = rest.find("\"\"\"") else {
+                return Err(self.error("unterminated triple-quoted string"));
+            };
+            // quotes immediately preceding the closing ones are part of the string
+            let len = len + rest[len..].len() - rest[len..].trim_start_matches('"').len() - 3;
+            self.position += 3 + len + 3;
+            return Ok(rest[..len].to_string());
+        }
+        let start = self.position;
+        self.position += 1;
+        let mut value = String::new();
+        loop {
+            let mut chars = self.rest().chars();
+            match chars.next() {
+                None | Some('\n') | Some('\r') => {
+                    self.position = start;
+                    return Err(self.error("unterminated string"));
+                }
+                Some('"') => {
+                    self.position += 1;
+                    return Ok(value);
+                }
+                Some('\\') => {
+                    let escaped = match chars.next() {
+                        Some('u') => {
+                            let code = self
+                                .rest()
+                                .get(2..6)
+                                .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                                .and_then(char::from_u32);
+                            let Some(c) = code else {
+                                return Err(self.error("invalid unicode escape"));
+                            };
+                            value.push(c);
+                            self.position += 6;
+                            continue;
+                        }
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\x08',
+                        Some('f') => '\x0c',
+                        Some(c @ ('"' | '\\' | '/')) => c,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    value.push(escaped);
+                    self.position += 2;
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.position += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn parse_unquoted(&mut self) -> &'t str {
+        let start = self.position;
+        let rest = self.rest();
+        let mut len = rest
+            .find(|c: char| !is_unquoted_char(c))
+            .unwrap_or(rest.len());
+        if let Some(comment) = rest[..len].find("//") {
+            len = comment;
+        }
+        self.position += len;
+        &self.text[start..self.position]
+    }
+
+    /// Parses a path expression such as 'a.b' or 'a."b.c"' into its keys. Quoted keys containing
+    /// '.' or '[' keep their quotes, so that flattening them does not make them paths.
+    fn parse_path(&mut self) -> Result<Vec<String>, InternalError> {
+        let mut keys: Vec<String> = Vec::new();
+        loop {
+            let key = if self.peek() == Some('"') {
+                let key = self.parse_quoted()?;
+                if key.contains(['.', '[']) {
+                    quote(&key)
+                } else {
+                    key
+                }
+            } else {
+                let start = self.position;
+                let key = self.parse_unquoted();
+                let key = match key.find('.') {
+                    Some(dot) => {
+                        self.position = start + dot;
+                        &key[..dot]
+                    }
+                    None => key,
+                };
+                if key.is_empty() {
+                    return Err(self.error("expected a key"));
+                }
+                key.to_string()
+            };
+            keys.push(key);
+            if self.peek() != Some('.') {
+                return Ok(keys);
+            }
+            self.position += 1;
+        }
+    }
+
+    /// Skips an include directive such as 'include required(file("a.conf"))', leaving it
+    /// unchanged in the document. Returns false if there is none at the current position.
+    fn skip_include(&mut self) -> Result<bool, InternalError> {
+        let Some(rest) = self.rest().strip_prefix("include") else {
+            return Ok(false);
+        };
+        let argument = rest.trim_start_matches([' ', '\t']);
+        if argument.len() == rest.len() {
+            return Ok(false);
+        }
+        self.position += self.rest().len() - argument.len();
+        if argument.starts_with('"') {
+            self.parse_quoted()?;
+            return Ok(true);
+        }
+        let mut depth = 0;
+        let end = argument.find(|c: char| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            c == ')' && depth == 0
+        });
+        match end {
+            Some(end) if argument.starts_with(char::is_alphabetic) => {
+                self.position += end + 1;
+                Ok(true)
+            }
+            _ => Err(self.error("invalid include")),
+        }
+    }
+
+    fn record_scalar(&mut self, key: String, value: String, start: usize, kind: ScalarKind) {
+        if self.recording {
+            self.scalars.push(Scalar {
+                key,
+                value,
+                start,
+                end: self.position,
+                kind,
+            });
+        }
+    }
+
+    fn parse_value(&mut self, key: String) -> Result<(), InternalError> {
+        match self.peek() {
+            Some('{') => return self.parse_container(key, ContainerKind::Object),
+            Some('[') => return self.parse_container(key, ContainerKind::Array),
+            _ => {}
+        }
+        let start = self.position;
+        let mut pieces: usize = 0;
+        let mut end = start;
+        let mut quoted: Option<String> = None;
+        let mut substituted = false;
+        loop {
+            match self.peek() {
+                Some('"') => quoted = Some(self.parse_quoted()?),
+                Some('$') if self.rest().starts_with("${") => {
+                    let Some(len) = self.rest().find('}') else {
+                        return Err(self.error("unterminated substitution"));
+                    };
+                    self.position += len + 1;
+                    substituted = true;
+                }
+                Some(c) if is_unquoted_char(c) && !self.rest().starts_with("//") => {
+                    self.parse_unquoted();
+                }
+                _ => break,
+            }
+            pieces += 1;
+            end = self.position;
+            // whitespace between the pieces of a concatenation is part of the value
+            self.skip_blank(false);
+            if self.position > end && self.text[end..self.position].contains(['#', '/']) {
+                break;
+            }
+        }
+        self.position = end;
+        if pieces == 0 {
+            return Err(self.error("expected a value"));
+        }
+        let raw = &self.text[start..end];
+        let (value, kind) = match (pieces, quoted) {
+            _ if substituted => (raw.to_string(), ScalarKind::Substitution),
+            (1, Some(quoted)) => (quoted, ScalarKind::Quoted),
+            (1, None) if raw == "true" || raw == "false" => (raw.to_string(), ScalarKind::Boolean),
+            (1, None) if raw == "null" => (String::new(), ScalarKind::Null),
+            (1, None) if is_json_number(raw, false) => (raw.to_string(), ScalarKind::Number),
+            (1, None) if !raw.starts_with('$') => (raw.to_string(), ScalarKind::Unquoted),
+            _ => (raw.to_string(), ScalarKind::Verbatim),
+        };
+        self.record_scalar(key, value, start, kind);
+        Ok(())
+    }
+
+    /// Parses an object or an array whose opening bracket is at the current position, or the
+    /// root object without braces if `key` is empty and there is no bracket.
+    fn parse_container(&mut self, key: String, kind: ContainerKind) -> Result<(), InternalError> {
+        let open = match self.peek() {
+            Some('{') | Some('[') => {
+                self.position += 1;
+                Some(self.position - 1)
+            }
+            _ => None,
+        };
+        let closing = match (open, kind) {
+            (None, _) => None,
+            (Some(_), ContainerKind::Object) => Some('}'),
+            (Some(_), ContainerKind::Array) => Some(']'),
+        };
+        let mut last_member_end: Option<usize> = None;
+        let mut member_indent: Option<String> = None;
+        let mut multiline = open.is_none();
+        let mut len: usize = 0;
+        loop {
+            self.skip_blank(true);
+            match self.peek() {
+                None if closing.is_none() => break,
+                None => return Err(self.error("unterminated object or array")),
+                Some(',') if last_member_end.is_some() => {
+                    self.position += 1;
+                    continue;
+                }
+                Some(c) if Some(c) == closing => break,
+                _ => {}
+            }
+            if member_indent.is_none() {
+                let before = &self.text[open.map(|o| o + 1).unwrap_or(0)..self.position];
+                multiline = multiline || before.contains(['\n', '\r']);
+                member_indent = Some(self.line_indent(self.position));
+            }
+            match kind {
+                ContainerKind::Array => {
+                    self.parse_value(format!("{}[{}]", key, len))?;
+                    len += 1;
+                }
+                ContainerKind::Object => self.parse_member(&key)?,
+            }
+            last_member_end = Some(self.position);
+            self.skip_blank(false);
+            match self.peek() {
+                None | Some('\n') | Some('\r') | Some(',') => {}
+                Some(c) if Some(c) == closing => {}
+                _ => return Err(self.error("expected ',' or a new line")),
+            }
+        }
+        let indent = self.line_indent(open.unwrap_or(0));
+        if let Some(member_indent) = &member_indent {
+            if self.step.is_none() && open.is_some() && member_indent.len() > indent.len() {
+                self.step = Some(member_indent[indent.len()..].to_string());
+            }
+        }
+        if self.recording {
+            self.containers.entry(key).or_insert(Container {
+                kind,
+                open,
+                close: self.position,
+                last_member_end,
+                multiline,
+                member_indent,
+                indent,
+            });
+        }
+        if closing.is_some() {
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn parse_member(&mut self, prefix: &str) -> Result<(), InternalError> {
+        if self.skip_include()? {
+            return Ok(());
+        }
+        let mut segments: Vec<Segment> = Vec::new();
+        if !prefix.is_empty() {
+            segments.push(Segment::Key(prefix.to_string()));
+        }
+        segments.extend(self.parse_path()?.into_iter().map(Segment::Key));
+        let key = segments_key(&segments);
+        self.skip_blank(false);
+        if self.peek() == Some('{') {
+            return self.parse_container(key, ContainerKind::Object);
+        }
+        let appending = self.rest().starts_with("+=");
+        match self.peek() {
+            Some('+') if appending => self.position += 2,
+            Some('=') | Some(':') => self.position += 1,
+            _ => return Err(self.error("expected '=', ':' or '{'")),
+        }
+        self.skip_blank(false);
+        let recording = self.recording;
+        self.recording = recording && !appending;
+        let result = self.parse_value(key);
+        self.recording = recording;
+        result
+    }
+}
+
+/// A HOCON document whose values are addressed by flattened keys: the keys of nested objects and
+/// path expressions are joined with '.' and the elements of arrays are addressed as '[n]'.
+/// Includes, comments, substitutions and formatting are left untouched: only the text of the
+/// updated values is rewritten, and added properties are inserted into the deepest existing
+/// object or array as path expressions such as 'a.b = 1'. Quoted keys containing '.' are kept
+/// quoted in flattened keys, as in 'a."b.c"', and numeric keys such as 'list.0' address the
+/// elements of arrays.
+#[derive(Debug)]
+pub struct HoconDocument<'a> {
+    text: &'a str,
+    scalars: Vec<Scalar>,
+    /// Replacement text of the updated scalars
+    replacements: HashMap<usize, String>,
+    /// Objects and arrays by flattened key, the root having an empty key
+    containers: HashMap<String, Container>,
+    step: String,
+    additions: Vec<Property<'a>>,
+}
+
+impl<'a> HoconDocument<'a> {
+    pub fn parse(text: &'a str) -> Result<HoconDocument<'a>, InternalError> {
+        let mut parser = HoconParser {
+            text,
+            position: 0,
+            scalars: Vec::new(),
+            containers: HashMap::new(),
+            step: None,
+            recording: true,
+        };
+        parser.skip_blank(true);
+        parser.parse_container(String::new(), ContainerKind::Object)?;
+        parser.skip_blank(true);
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected characters after the root object"));
+        }
+        Ok(HoconDocument {
+            text,
+            scalars: parser.scalars,
+            replacements: HashMap::new(),
+            containers: parser.containers,
+            step: parser.step.unwrap_or(DEFAULT_INDENT_STEP.to_string()),
+            additions: Vec::new(),
+        })
+    }
+
+    /// Edits inserting the added properties into their containers, as replaced byte ranges of the
+    /// text and their replacement.
+    fn addition_edits(&self) -> Vec<(usize, usize, String)> {
+        let scalar_keys: HashSet<&str> = self.scalars.iter().map(|s| s.key.as_str()).collect();
+        let mut trees: Vec<(&Container, Nodes)> = Vec::new();
+        for property in &self.additions {
+            let mut segments = key_segments(&property.key);
+            // path expressions address the elements of arrays by number, as in 'list.0'
+            for k in 1..segments.len() {
+                let in_array = self
+                    .containers
+                    .get(&segments_key(&segments[..k]))
+                    .is_some_and(|c| c.kind == ContainerKind::Array);
+                let index = match &segments[k] {
+                    Segment::Key(name) if in_array && name.bytes().all(|b| b.is_ascii_digit()) => {
+                        name.parse().ok()
+                    }
+                    _ => None,
+                };
+                if let Some(index) = index {
+                    segments[k] = Segment::Index(index);
+                }
+            }
+            let attach = (0..segments.len()).rev().find_map(|n| {
+                self.containers
+                    .get(&segments_key(&segments[..n]))
+                    .filter(|c| c.kind == container_kind(&segments[n]))
+                    .map(|c| (n, c))
+            });
+            // the root is always an object, so only keys starting with an index have no container
+            let Some((n, container)) = attach else {
+                continue;
+            };
+            // keys nested under an existing value cannot be expanded into objects
+            let remaining = if (n + 1..segments.len()).any(|k| {
+                let prefix = segments_key(&segments[..k]);
+                scalar_keys.contains(prefix.as_str()) || self.containers.contains_key(&prefix)
+            }) {
+                vec![Segment::Key(segments_key(&segments[n..]))]
+            } else {
+                segments[n..].to_vec()
+            };
+            let index = match trees.iter().position(|(c, _)| std::ptr::eq(*c, container)) {
+                Some(index) => index,
+                None => {
+                    trees.push((container, Vec::new()));
+                    trees.len() - 1
+                }
+            };
+            insert_node(&mut trees[index].1, &remaining, &property.value);
+        }
+        let mut edits: Vec<(usize, usize, String)> = Vec::new();
+        for (container, children) in trees {
+            let multiline = container.multiline
+                || (container.last_member_end.is_none() && self.text.contains('\n'));
+            let layout = Layout {
+                multiline,
+                step: self.step.clone(),
+            };
+            let member_indent = container
+                .member_indent
+                .clone()
+                .unwrap_or_else(|| format!("{}{}", container.indent, layout.step));
+            let members = layout.render_children(&children, &member_indent);
+            let edit = match (container.last_member_end, container.open) {
+                (Some(end), _) if multiline => {
+                    // after the comments following the last member
+                    let line_end = self.text[end..container.close]
+                        .find(['\n', '\r'])
+                        .map(|i| end + i)
+                        .unwrap_or(container.close);
+                    let inserted: String = members
+                        .iter()
+                        .map(|member| format!("\n{}{}", member_indent, member))
+                        .collect();
+                    (line_end, line_end, inserted)
+                }
+                (Some(end), _) => {
+                    let inserted: String = members
+                        .iter()
+                        .map(|member| format!(", {}", member))
+                        .collect();
+                    (end, end, inserted)
+                }
+                (None, Some(open)) if multiline => (
+                    open + 1,
+                    container.close,
+                    format!(
+                        "\n{}{}\n{}",
+                        member_indent,
+                        members.join(format!("\n{}", member_indent).as_str()),
+                        container.indent
+                    ),
+                ),
+                (None, Some(open)) => (
+                    open + 1,
+                    container.close,
+                    format!(" {} ", members.join(", ")),
+                ),
+                (None, None) => {
+                    let separator = if self.text.is_empty() || self.text.ends_with(['\n', '\r']) {
+                        ""
+                    } else {
+                        "\n"
+                    };
+                    (
+                        self.text.len(),
+                        self.text.len(),
+                        format!("{}{}\n", separator, members.join("\n")),
+                    )
+                }
+            };
+            edits.push(edit);
+        }
+        edits
+    }
+}
+
+impl<'a> Document<'a> for HoconDocument<'a> {
+    /// The scalars of the document, including values made of substitutions such as '${HOME}'
+    /// whose value is their source text.
+    fn properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.scalars
+            .iter()
+            .enumerate()
+            .map(|(index, scalar)| {
+                (
+                    index,
+                    Property {
+                        key: Cow::Borrowed(scalar.key.as_str()),
+                        value: Cow::Borrowed(scalar.value.as_str()),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Replaces the value at `index`, keeping its type and quoting when `value` can be written
+    /// the same way.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError> {
+        let scalar = &mut self.scalars[index];
+        self.replacements
+            .insert(index, typed_value(value, scalar.kind));
+        scalar.value = value.to_string();
+        Ok(())
+    }
+
+    /// The values made of substitutions which are not overridden.
+    fn unresolved_properties(&self) -> Vec<(usize, Property<'_>)> {
+        Document::properties(self)
+            .into_iter()
+            .filter(|(index, _)| {
+                self.scalars[*index].kind == ScalarKind::Substitution
+                    && !self.replacements.contains_key(index)
+            })
+            .collect()
+    }
+
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError> {
+        self.additions.push(property);
+        Ok(())
+    }
+
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()> {
+        let mut edits = self.addition_edits();
+        for (index, replacement) in &self.replacements {
+            let scalar = &self.scalars[*index];
+            edits.push((scalar.start, scalar.end, replacement.clone()));
+        }
+        edits.sort_by_key(|(start, end, _)| (*start, *end));
+        let mut rendered = String::with_capacity(self.text.len());
+        let mut position: usize = 0;
+        for (start, end, replacement) in edits {
+            rendered.push_str(&self.text[position..start]);
+            rendered.push_str(replacement.as_str());
+            position = end;
+        }
+        rendered.push_str(&self.text[position..]);
+        let mut lines = split_physical_lines(&rendered);
+        if lines.last() == Some(&"") {
+            lines.pop();
+        }
+        for line in lines {
+            output.write_all(line.as_bytes())?;
+            output.write_all(line_ending.as_str().as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod hocon_document_tests {
+    use super::*;
+
+    fn parse(s: &str) -> HoconDocument<'_> {
+        HoconDocument::parse(s).unwrap()
+    }
+
+    fn properties(document: &HoconDocument) -> Vec<(String, String)> {
+        Document::properties(document)
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect()
+    }
+
+    fn write(document: &HoconDocument) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        Document::write(document, &mut output, LineEnding::Lf).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    const INPUT: &str = "include \"base.conf\"\ninclude required(file(\"a.conf\"))\n# comment\nserver {\n  port = 8080 // default\n  name: \"app\\n\"\n  timeouts { read = 10s, write = 20 }\n}\na.b.c = INFO\n\"x.y\" = true\nhome = ${HOME}\"/data\"\nopt = ${?OPT}\nlist = [1, { k = v }]\nlist += 3\nnothing = null\ntext = \"\"\"say \"hi\"\"\"\"\n";
+
+    #[test]
+    fn parse_should_flatten_objects_paths_and_arrays() {
+        assert_eq!(
+            properties(&parse(INPUT)),
+            pairs(&[
+                ("server.port", "8080"),
+                ("server.name", "app\n"),
+                ("server.timeouts.read", "10s"),
+                ("server.timeouts.write", "20"),
+                ("a.b.c", "INFO"),
+                ("\"x.y\"", "true"),
+                ("home", "${HOME}\"/data\""),
+                ("opt", "${?OPT}"),
+                ("list[0]", "1"),
+                ("list[1].k", "v"),
+                ("nothing", ""),
+                ("text", "say \"hi\""),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_should_accept_root_braces_and_blank_input() {
+        assert_eq!(
+            properties(&parse("{ \"a\": { \"b\": 1 } }")),
+            pairs(&[("a.b", "1")])
+        );
+        assert_eq!(properties(&parse("  \n")), pairs(&[]));
+    }
+
+    #[test]
+    fn parse_should_report_errors_with_position() {
+        for (input, line, column, message) in [
+            ("a {\n  b = 1\n", 3, 1, "unterminated object or array"),
+            ("a = \"x\n", 1, 5, "unterminated string"),
+            ("a b = 1", 1, 3, "expected '=', ':' or '{'"),
+            ("a = 1 }", 1, 7, "expected ',' or a new line"),
+            ("a = ${x", 1, 5, "unterminated substitution"),
+        ] {
+            match HoconDocument::parse(input).unwrap_err() {
+                InternalError::ParseError {
+                    line_num,
+                    snippet,
+                    message: m,
+                } => assert_eq!(
+                    (line_num, snippet.unwrap().column, m.as_str()),
+                    (line, column, message),
+                    "{}",
+                    input
+                ),
+                _ => panic!("error is not ParseError"),
+            }
+        }
+    }
+
+    #[test]
+    fn write_should_reproduce_input_exactly() {
+        assert_eq!(write(&parse(INPUT)), INPUT);
+    }
+
+    #[test]
+    fn set_value_should_keep_type_and_quoting_when_possible() {
+        let mut document = parse("a = 1\nb = true\nc = \"x\"\nd = INFO\ne = ${?E}\nf = INFO\n");
+
+        document.set_value(0, "2").unwrap();
+        document.set_value(1, "no").unwrap();
+        document.set_value(2, "y").unwrap();
+        document.set_value(3, "DEBUG").unwrap();
+        document.set_value(4, "5").unwrap();
+        document.set_value(5, "a: b").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "a = 2\nb = \"no\"\nc = \"y\"\nd = DEBUG\ne = 5\nf = \"a: b\"\n"
+        );
+    }
+
+    #[test]
+    fn unresolved_properties_should_return_substitutions_which_are_not_overridden() {
+        let mut document = parse("a = ${?A}\nb = ${HOME}/app\nc = \"${x}\"\nd = x y\ne = ${?E}\n");
+
+        document.set_value(4, "1").unwrap();
+        let unresolved: Vec<(String, String)> = document
+            .unresolved_properties()
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect();
+
+        assert_eq!(unresolved, pairs(&[("a", "${?A}"), ("b", "${HOME}/app")]));
+    }
+
+    #[test]
+    fn push_should_insert_into_deepest_object_as_paths() {
+        let mut document = parse("server {\n    port = 8080 # port\n    empty {}\n}\nlist = [1]\n");
+
+        document
+            .push(Property::new("server.ssl.enabled", "true"))
+            .unwrap();
+        document.push(Property::new("server.empty.a", "x")).unwrap();
+        document.push(Property::new("list[1]", "2")).unwrap();
+        document
+            .push(Property::new("logging.level.root", "info"))
+            .unwrap();
+        document
+            .push(Property::new("logging.level.web", "debug"))
+            .unwrap();
+
+        assert_eq!(
+            write(&document),
+            "server {\n    port = 8080 # port\n    empty {\n        a = \"x\"\n    }\n    ssl.enabled = true\n}\nlist = [1, 2]\nlogging.level {\n    root = \"info\"\n    web = \"debug\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn parse_should_keep_quotes_of_keys_containing_dots() {
+        assert_eq!(
+            properties(&parse(
+                "akka {\n  \"quoted.key\" = 1\n  \"plain\" = 2\n  a.\"b[0]\" = 3\n}\n"
+            )),
+            pairs(&[
+                ("akka.\"quoted.key\"", "1"),
+                ("akka.plain", "2"),
+                ("akka.a.\"b[0]\"", "3"),
+            ])
+        );
+    }
+
+    #[test]
+    fn push_should_insert_numeric_keys_under_arrays_as_elements() {
+        let mut document = parse("akka {\n  list = [1, { k = v }]\n}\n");
+
+        document.push(Property::new("akka.list.2", "9")).unwrap();
+        document.push(Property::new("akka.list.1.x", "y")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "akka {\n  list = [1, { k = v, x = \"y\" }, 9]\n}\n"
+        );
+    }
+
+    #[test]
+    fn push_should_add_keys_nested_under_values_as_quoted_keys() {
+        let mut document = parse("a = 1");
+
+        document.push(Property::new("a.b", "x")).unwrap();
+        document.push(Property::new("c", "y")).unwrap();
+
+        assert_eq!(write(&document), "a = 1\n\"a.b\" = \"x\"\nc = \"y\"\n");
+    }
+}
@@ -0,0 +1,558 @@
+use crate::document::{removed_duplicates, Document};
+use crate::line_ending::split_physical_lines;
+use crate::model::{DuplicatePolicy, Format, InternalError, LineEnding, Property};
+use std::borrow::Cow;
+use std::io;
+use std::io::Write;
+
+/// Separator of added properties when the document has no assignment to take it from.
+const DEFAULT_SEPARATOR: &str = " = ";
+
+#[derive(Debug, PartialEq)]
+enum Kind<'a> {
+    /// Blank and comment lines
+    Ignorable,
+    Section(Cow<'a, str>),
+    /// A 'key = value' line, whose property key is prefixed with the name of its section
+    Assignment {
+        property: Property<'a>,
+        value_start: usize,
+        has_separator: bool,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+struct Entry<'a> {
+    line_num: i32,
+    text: Cow<'a, str>,
+    kind: Kind<'a>,
+}
+
+fn section_key<'a>(section: &str, key: &'a str) -> Cow<'a, str> {
+    if section.is_empty() {
+        Cow::Borrowed(key)
+    } else {
+        Cow::Owned(format!("{}.{}", section, key))
+    }
+}
+
+/// Verifies that `value` is read back unchanged once written after a separator: INI files have
+/// no escape sequences, so that line breaks would end the line and surrounding whitespace would be
+/// trimmed.
+fn check_value(key: &str, value: &str) -> Result<(), InternalError> {
+    let reason = if value.contains(['\n', '\r']) {
+        "its value contains a line break"
+    } else if value != value.trim() {
+        "its value has leading or trailing whitespace"
+    } else {
+        return Ok(());
+    };
+    Err(InternalError::unrepresentable_property(
+        key,
+        Format::Ini,
+        reason,
+    ))
+}
+
+/// Parses a line within `section`, returning the byte offset and description of the error if
+/// the line is malformed.
+fn parse_line<'a>(line: &'a str, section: &str) -> Result<Kind<'a>, (usize, &'static str)> {
+    let content = line.trim_start();
+    let content_start = line.len() - content.len();
+    if content.is_empty() || content.starts_with([';', '#']) {
+        return Ok(Kind::Ignorable);
+    }
+    if let Some(header) = content.strip_prefix('[') {
+        let Some(end) = header.find(']') else {
+            return Err((content_start, "unterminated section header"));
+        };
+        let name = header[..end].trim();
+        if name.is_empty() {
+            return Err((content_start, "empty section name"));
+        }
+        let trailing = header[end + 1..].trim_start();
+        if !trailing.is_empty() && !trailing.starts_with([';', '#']) {
+            return Err((
+                line.len() - trailing.len(),
+                "unexpected characters after the section header",
+            ));
+        }
+        return Ok(Kind::Section(Cow::Borrowed(name)));
+    }
+    let (key, value_start, has_separator) = match content.find(['=', ':']) {
+        Some(separator) => {
+            let after = &line[content_start + separator + 1..];
+            (
+                content[..separator].trim_end(),
+                line.len() - after.trim_start().len(),
+                true,
+            )
+        }
+        None => {
+            let key = content.trim_end();
+            (key, content_start + key.len(), false)
+        }
+    };
+    if key.is_empty() {
+        return Err((content_start, "expected a key"));
+    }
+    Ok(Kind::Assignment {
+        property: Property {
+            key: section_key(section, key),
+            value: Cow::Borrowed(line[value_start..].trim_end()),
+        },
+        value_start,
+        has_separator,
+    })
+}
+
+/// An INI file whose keys are addressed as 'section.key', or as 'key' before the first section
+/// header. Lines starting with ';' or '#' are comments. Every line is kept verbatim and only the
+/// values of the updated properties are rewritten, while added properties are placed at the end
+/// of their section, creating it after the existing ones when missing.
+#[derive(Debug, PartialEq)]
+pub struct IniDocument<'a> {
+    entries: Vec<Entry<'a>>,
+    /// Separator between key and value, taken from the first assignment of the document
+    separator: String,
+}
+
+impl<'a> IniDocument<'a> {
+    /// Parses all the lines of `text`, reporting every malformed line in a single
+    /// `InternalError::ParseErrors`.
+    pub fn parse(text: &'a str) -> Result<IniDocument<'a>, InternalError> {
+        let mut entries: Vec<Entry<'a>> = Vec::new();
+        let mut errors: Vec<InternalError> = Vec::new();
+        let mut separator: Option<String> = None;
+        let mut section: &'a str = "";
+        let mut lines = split_physical_lines(text);
+        if lines.last() == Some(&"") {
+            lines.pop();
+        }
+        for (index, line) in lines.into_iter().enumerate() {
+            let line_num = index as i32 + 1;
+            let kind = match parse_line(line, section) {
+                Ok(kind) => kind,
+                Err((offset, message)) => {
+                    let column = line[..offset].chars().count() as i32 + 1;
+                    errors.push(InternalError::parse_error_at(
+                        line_num, column, line, message,
+                    ));
+                    Kind::Ignorable
+                }
+            };
+            match &kind {
+                Kind::Section(Cow::Borrowed(name)) => section = name,
+                Kind::Assignment {
+                    property,
+                    value_start,
+                    has_separator: true,
+                } if separator.is_none() && !property.value.is_empty() => {
+                    let key_end = line[..*value_start]
+                        .find(['=', ':'])
+                        .map(|separator| line[..separator].trim_end().len())
+                        .unwrap_or(0);
+                    separator = Some(line[key_end..*value_start].to_string());
+                }
+                _ => {}
+            }
+            entries.push(Entry {
+                line_num,
+                text: Cow::Borrowed(line),
+                kind,
+            });
+        }
+        if !errors.is_empty() {
+            return Err(InternalError::ParseErrors(errors));
+        }
+        Ok(IniDocument {
+            entries,
+            separator: separator.unwrap_or(DEFAULT_SEPARATOR.to_string()),
+        })
+    }
+
+    /// The name of the section of every entry, empty before the first section header.
+    fn entry_sections(&self) -> Vec<String> {
+        let mut section = String::new();
+        self.entries
+            .iter()
+            .map(|entry| {
+                if let Kind::Section(name) = &entry.kind {
+                    section = name.to_string();
+                }
+                section.clone()
+            })
+            .collect()
+    }
+
+    fn assignment_entry(&self, line_num: i32, key: &str, property: Property<'a>) -> Entry<'a> {
+        let text = format!("{}{}{}", key, self.separator, property.value);
+        Entry {
+            line_num,
+            kind: Kind::Assignment {
+                value_start: text.len() - property.value.len(),
+                property,
+                has_separator: true,
+            },
+            text: Cow::Owned(text),
+        }
+    }
+}
+
+impl<'a> Document<'a> for IniDocument<'a> {
+    fn properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match &entry.kind {
+                Kind::Assignment { property, .. } => Some((index, property.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Applies `policy` to the keys defined more than once in the same section, removing the
+    /// lines of the assignments which are not retained.
+    fn resolve_duplicates(&mut self, policy: DuplicatePolicy) -> Result<(), InternalError> {
+        let definitions = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match &entry.kind {
+                Kind::Assignment { property, .. } => {
+                    Some((index, property.key.as_ref(), entry.line_num))
+                }
+                _ => None,
+            });
+        let removed = removed_duplicates(definitions, policy)?;
+        let mut index: usize = 0;
+        self.entries.retain(|_| {
+            let keep = !removed.contains(&index);
+            index += 1;
+            keep
+        });
+        Ok(())
+    }
+
+    /// Replaces the value of the assignment at `index`, leaving key and separator untouched.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError> {
+        let separator = self.separator.clone();
+        let entry = &mut self.entries[index];
+        if let Kind::Assignment {
+            property,
+            value_start,
+            has_separator,
+        } = &mut entry.kind
+        {
+            check_value(&property.key, value)?;
+            let text = entry.text.to_mut();
+            text.truncate(*value_start);
+            if !*has_separator {
+                text.push_str(separator.as_str());
+                *value_start += separator.len();
+                *has_separator = true;
+            }
+            text.push_str(value);
+            property.value = Cow::Owned(value.to_string());
+        }
+        Ok(())
+    }
+
+    /// Assignments commented out in comment lines such as ';key = value', with the index of their
+    /// entry. Their keys are prefixed with the name of the section of the comment.
+    fn commented_properties(&self) -> Vec<(usize, Property<'_>)> {
+        let sections = self.entry_sections();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                if entry.kind != Kind::Ignorable {
+                    return None;
+                }
+                let uncommented = entry
+                    .text
+                    .trim_start()
+                    .strip_prefix([';', '#'])?
+                    .trim_start();
+                match parse_line(uncommented, &sections[index]) {
+                    Ok(Kind::Assignment {
+                        property,
+                        has_separator: true,
+                        ..
+                    }) => Some((index, property.into_owned())),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Replaces the comment line at `index` with the commented out assignment, retaining its
+    /// indentation, key and separator.
+    fn activate(&mut self, index: usize, property: Property<'a>) -> Result<(), InternalError> {
+        check_value(&property.key, &property.value)?;
+        let entry = &self.entries[index];
+        let indentation_len = entry.text.len() - entry.text.trim_start().len();
+        let uncommented = entry.text[indentation_len + 1..].trim_start();
+        let Ok(Kind::Assignment { value_start, .. }) = parse_line(uncommented, "") else {
+            return Ok(());
+        };
+        let text = format!(
+            "{}{}{}",
+            &entry.text[..indentation_len],
+            &uncommented[..value_start],
+            property.value
+        );
+        self.entries[index] = Entry {
+            line_num: entry.line_num,
+            kind: Kind::Assignment {
+                value_start: text.len() - property.value.len(),
+                property,
+                has_separator: true,
+            },
+            text: Cow::Owned(text),
+        };
+        Ok(())
+    }
+
+    /// Adds the property after the last assignment of the longest section prefixing its key.
+    /// Keys without a matching section are added before the first section header when they
+    /// have no '.' or the document has no sections, otherwise to a new section named after the
+    /// key up to its last '.'.
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError> {
+        check_value(&property.key, &property.value)?;
+        let sections = self.entry_sections();
+        let matching = sections
+            .iter()
+            .map(String::as_str)
+            .filter(|section| {
+                !section.is_empty()
+                    && property.key.len() > section.len() + 1
+                    && property.key.starts_with(section)
+                    && property.key[section.len()..].starts_with('.')
+            })
+            .max_by_key(|section| section.len());
+        let has_sections = sections.iter().any(|section| !section.is_empty());
+        let section = match matching {
+            Some(section) => section,
+            None if !has_sections || !property.key.contains('.') => "",
+            None => {
+                let (section, key) = property.key.rsplit_once('.').unwrap();
+                let key = key.to_string();
+                let line_num = self.entries.last().map(|e| e.line_num + 1).unwrap_or(1);
+                if self
+                    .entries
+                    .last()
+                    .is_some_and(|e| !e.text.trim().is_empty())
+                {
+                    self.entries.push(Entry {
+                        line_num,
+                        text: Cow::Borrowed(""),
+                        kind: Kind::Ignorable,
+                    });
+                }
+                self.entries.push(Entry {
+                    line_num,
+                    text: Cow::Owned(format!("[{}]", section)),
+                    kind: Kind::Section(Cow::Owned(section.to_string())),
+                });
+                let entry = self.assignment_entry(line_num, &key, property);
+                self.entries.push(entry);
+                return Ok(());
+            }
+        };
+        let in_section = |index: &usize| sections[*index] == section;
+        let last_assignment = (0..self.entries.len())
+            .filter(in_section)
+            .rfind(|index| matches!(self.entries[*index].kind, Kind::Assignment { .. }));
+        let position = match last_assignment {
+            Some(index) => index + 1,
+            // sections without assignments receive them right after their header, and keys
+            // without a section before the first header
+            None if section.is_empty() => sections
+                .iter()
+                .position(|s| !s.is_empty())
+                .unwrap_or(self.entries.len()),
+            None => (0..self.entries.len()).find(in_section).unwrap() + 1,
+        };
+        let key = if section.is_empty() {
+            property.key.to_string()
+        } else {
+            property.key[section.len() + 1..].to_string()
+        };
+        let line_num = self.entries.get(position).map(|e| e.line_num).unwrap_or(1);
+        let entry = self.assignment_entry(line_num, &key, property);
+        self.entries.insert(position, entry);
+        Ok(())
+    }
+
+    /// Writes the document terminating every line with `line_ending`.
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()> {
+        for entry in &self.entries {
+            output.write_all(entry.text.as_bytes())?;
+            output.write_all(line_ending.as_str().as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod ini_document_tests {
+    use super::*;
+
+    fn parse(s: &str) -> IniDocument<'_> {
+        IniDocument::parse(s).unwrap()
+    }
+
+    fn pairs_of(properties: Vec<(usize, Property)>) -> Vec<(String, String)> {
+        properties
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect()
+    }
+
+    fn write(document: &IniDocument) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        document.write(&mut output, LineEnding::Lf).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    const INPUT: &str =
+        "; global\nname = app\n\n[server]\nport: 8080\n  host=a=b ; not a comment\nflag\n\n[server.ssl]\n# enabled = false\n";
+
+    #[test]
+    fn parse_should_prefix_keys_with_sections() {
+        assert_eq!(
+            pairs_of(parse(INPUT).properties()),
+            pairs(&[
+                ("name", "app"),
+                ("server.port", "8080"),
+                ("server.host", "a=b ; not a comment"),
+                ("server.flag", ""),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_should_report_all_errors() {
+        match IniDocument::parse("a=1\n[s\n = 2\n[x] y\n") {
+            Err(InternalError::ParseErrors(errors)) => assert_eq!(
+                errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+                vec![
+                    "cannot parse property at line 2, column 1: unterminated section header\n[s\n^",
+                    "cannot parse property at line 3, column 2: expected a key\n = 2\n ^",
+                    "cannot parse property at line 4, column 5: unexpected characters after the section header\n[x] y\n    ^",
+                ]
+            ),
+            _ => panic!("result is not ParseErrors"),
+        }
+    }
+
+    #[test]
+    fn write_should_reproduce_input_exactly() {
+        assert_eq!(write(&parse(INPUT)), INPUT);
+    }
+
+    #[test]
+    fn set_value_should_keep_key_and_separator() {
+        let mut document = parse(INPUT);
+
+        document.set_value(4, "9090").unwrap();
+        document.set_value(6, "x").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "; global\nname = app\n\n[server]\nport: 9090\n  host=a=b ; not a comment\nflag = x\n\n[server.ssl]\n# enabled = false\n"
+        );
+    }
+
+    #[test]
+    fn set_value_should_reject_values_with_line_breaks() {
+        let mut document = parse(INPUT);
+
+        assert_eq!(
+            document.set_value(4, "80\n[x]").unwrap_err().to_string(),
+            "property 'server.port' cannot be represented in the ini format: its value contains a line break"
+        );
+        assert_eq!(write(&document), INPUT);
+    }
+
+    #[test]
+    fn push_should_reject_values_with_surrounding_whitespace() {
+        let mut document = parse(INPUT);
+
+        assert_eq!(
+            document
+                .push(Property::new("server.prompt", "> "))
+                .unwrap_err()
+                .to_string(),
+            "property 'server.prompt' cannot be represented in the ini format: its value has leading or trailing whitespace"
+        );
+        assert!(document.push(Property::new("a", " b")).is_err());
+        assert_eq!(write(&document), INPUT);
+    }
+
+    #[test]
+    fn resolve_duplicates_should_only_consider_keys_of_same_section() {
+        let mut document = parse("[a]\nk=1\n[b]\nk=2\n[a]\nk=3\n");
+
+        document
+            .resolve_duplicates(DuplicatePolicy::LastWins)
+            .unwrap();
+
+        assert_eq!(write(&document), "[a]\n[b]\nk=2\n[a]\nk=3\n");
+    }
+
+    #[test]
+    fn activate_should_replace_commented_assignments_of_section() {
+        let mut document = parse(INPUT);
+
+        let commented = pairs_of(document.commented_properties());
+        document
+            .activate(9, Property::new("server.ssl.enabled", "true"))
+            .unwrap();
+
+        assert_eq!(commented, pairs(&[("server.ssl.enabled", "false")]));
+        assert!(write(&document).ends_with("[server.ssl]\nenabled = true\n"));
+    }
+
+    #[test]
+    fn push_should_place_properties_in_their_section() {
+        let mut document = parse(INPUT);
+
+        document.push(Property::new("server.timeout", "5")).unwrap();
+        document
+            .push(Property::new("server.ssl.enabled", "true"))
+            .unwrap();
+        document.push(Property::new("version", "1")).unwrap();
+        document
+            .push(Property::new("logging.level.root", "info"))
+            .unwrap();
+        document
+            .push(Property::new("logging.level.web", "debug"))
+            .unwrap();
+
+        assert_eq!(
+            write(&document),
+            "; global\nname = app\nversion = 1\n\n[server]\nport: 8080\n  host=a=b ; not a comment\nflag\ntimeout = 5\n\n[server.ssl]\nenabled = true\n# enabled = false\n\n[logging.level]\nroot = info\nweb = debug\n"
+        );
+    }
+
+    #[test]
+    fn push_should_add_global_keys_before_first_section() {
+        let mut without_globals = parse("; c\n[a]\nk=1\n");
+        let mut without_sections = parse("x=1\n");
+
+        without_globals.push(Property::new("g", "1")).unwrap();
+        without_sections.push(Property::new("a.b", "2")).unwrap();
+
+        assert_eq!(write(&without_globals), "; c\ng=1\n[a]\nk=1\n");
+        assert_eq!(write(&without_sections), "x=1\na.b=2\n");
+    }
+}
@@ -0,0 +1,1022 @@
+use crate::document::{removed_duplicates, Document};
+use crate::keys::{insert_node, key_segments, segments_key, Node, Nodes, Segment};
+use crate::line_ending::split_physical_lines;
+use crate::model::{DuplicatePolicy, Format, InternalError, LineEnding, Property};
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::Write;
+
+/// Indentation of nested objects added to documents which contain none.
+const DEFAULT_INDENT_STEP: &str = "  ";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ScalarKind {
+    String,
+    Number,
+    Boolean,
+    Null,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ContainerKind {
+    Object,
+    Array,
+}
+
+/// A scalar of the document with the flattened key it is reachable with, such as 'a.b[1].c'.
+#[derive(Debug)]
+struct Scalar {
+    key: String,
+    value: String,
+    /// Byte offset in the text where the scalar starts
+    start: usize,
+    /// Byte offset in the text where the scalar ends
+    end: usize,
+    kind: ScalarKind,
+}
+
+/// An object or an array of the document, where added properties are inserted.
+#[derive(Debug)]
+struct Container {
+    kind: ContainerKind,
+    /// Byte offset of the opening bracket
+    open: usize,
+    /// Byte offset of the closing bracket
+    close: usize,
+    /// Byte offset where the value of the last member ends, if the container is not empty
+    last_value_end: Option<usize>,
+    /// Whitespace preceding the first member, reused to separate added members
+    separator: String,
+    /// Indentation of the line where the container starts
+    indent: String,
+}
+
+/// A member of an object, with the byte range from its name to the end of its value.
+#[derive(Debug)]
+struct Member {
+    /// Flattened key of the member, such as 'a.b'
+    key: String,
+    /// Byte offset of the opening bracket of its object
+    object: usize,
+    start: usize,
+    end: usize,
+}
+
+impl Segment {
+    fn container_kind(&self) -> ContainerKind {
+        match self {
+            Segment::Key(_) => ContainerKind::Object,
+            Segment::Index(_) => ContainerKind::Array,
+        }
+    }
+}
+
+/// Whether `text` follows the grammar of JSON numbers, allowing only integers if `integer`.
+pub fn is_json_number(text: &str, integer: bool) -> bool {
+    let digits = |s: &str| s.len() - s.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let unsigned = text.strip_prefix('-').unwrap_or(text);
+    let int_len = digits(unsigned);
+    if int_len == 0 || (int_len > 1 && unsigned.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &unsigned[int_len..];
+    if integer {
+        return rest.is_empty();
+    }
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let fraction_len = digits(fraction);
+        if fraction_len == 0 {
+            return false;
+        }
+        rest = &fraction[fraction_len..];
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        let exponent_len = digits(exponent);
+        if exponent_len == 0 {
+            return false;
+        }
+        rest = &exponent[exponent_len..];
+    }
+    rest.is_empty()
+}
+
+/// Renders `text` as a JSON string.
+pub fn quote(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\x08' => result.push_str("\\b"),
+            '\x0c' => result.push_str("\\f"),
+            c if (c as u32) < 0x20 => result.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Renders `text` as an integer or a boolean when it is written exactly as one, otherwise as a
+/// string.
+pub fn inferred_value(text: &str) -> String {
+    if is_json_number(text, true) || text == "true" || text == "false" {
+        text.to_string()
+    } else {
+        quote(text)
+    }
+}
+
+/// Renders `text` as a value of the same kind as the value it replaces when it represents one,
+/// otherwise as a string.
+fn typed_value(text: &str, kind: ScalarKind) -> String {
+    match kind {
+        ScalarKind::Number if is_json_number(text, false) => text.to_string(),
+        ScalarKind::Boolean if text == "true" || text == "false" => text.to_string(),
+        ScalarKind::Null => inferred_value(text),
+        _ => quote(text),
+    }
+}
+
+/// Formatting of the content added to the document, detected from the existing content.
+struct Layout {
+    multiline: bool,
+    step: String,
+    colon: String,
+}
+
+impl Layout {
+    /// Renders the node at the given indentation.
+    fn render(&self, node: &Node, indent: &str) -> String {
+        match node {
+            Node::Value(value) => inferred_value(value),
+            Node::Children(children) => {
+                let (open, close) = match children.first() {
+                    Some((Segment::Index(_), _)) => ("[", "]"),
+                    _ => ("{", "}"),
+                };
+                let member_indent = format!("{}{}", indent, self.step);
+                let (separator, closing) = if self.multiline {
+                    (format!("\n{}", member_indent), format!("\n{}", indent))
+                } else {
+                    (String::new(), String::new())
+                };
+                let members = self.render_members(children, &member_indent);
+                let joiner = if self.multiline { "," } else { ", " };
+                let members: Vec<String> = members
+                    .into_iter()
+                    .map(|member| format!("{}{}", separator, member))
+                    .collect();
+                format!("{}{}{}{}", open, members.join(joiner), closing, close)
+            }
+        }
+    }
+
+    /// Renders the members of an object or the elements of an array at the given indentation.
+    fn render_members(&self, children: &Nodes, indent: &str) -> Vec<String> {
+        children
+            .iter()
+            .map(|(segment, node)| match segment {
+                Segment::Key(key) => {
+                    format!("{}{}{}", quote(key), self.colon, self.render(node, indent))
+                }
+                Segment::Index(_) => self.render(node, indent),
+            })
+            .collect()
+    }
+}
+
+struct JsonParser<'t> {
+    text: &'t str,
+    position: usize,
+    scalars: Vec<Scalar>,
+    containers: HashMap<String, Container>,
+    /// Containers whose key is already the key of a preceding container
+    shadowed: Vec<(String, Container)>,
+    members: Vec<Member>,
+    colon: Option<String>,
+    step: Option<String>,
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> InternalError {
+        InternalError::parse_error_at_offset(self.text, self.position, message)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.position).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, message: &str) -> Result<(), InternalError> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(message));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    fn line_indent(&self, offset: usize) -> String {
+        let line_start = self.text[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line = &self.text[line_start..offset];
+        line[..line.len() - line.trim_start_matches([' ', '\t']).len()].to_string()
+    }
+
+    fn parse_value(&mut self, key: String) -> Result<(), InternalError> {
+        let start = self.position;
+        let kind = match self.peek() {
+            Some(b'{') => return self.parse_container(key, ContainerKind::Object),
+            Some(b'[') => return self.parse_container(key, ContainerKind::Array),
+            Some(b'"') => {
+                let value = self.parse_string()?;
+                self.scalars.push(Scalar {
+                    key,
+                    value,
+                    start,
+                    end: self.position,
+                    kind: ScalarKind::String,
+                });
+                return Ok(());
+            }
+            Some(b't' | b'f') => ScalarKind::Boolean,
+            Some(b'n') => ScalarKind::Null,
+            Some(b'-' | b'0'..=b'9') => ScalarKind::Number,
+            None => return Err(self.error("unexpected end of input")),
+            Some(_) => return Err(self.error("expected a value")),
+        };
+        let len = self.text[start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || "+-.".contains(c)))
+            .unwrap_or(self.text.len() - start);
+        let literal = &self.text[start..start + len];
+        let valid = match kind {
+            ScalarKind::Boolean => literal == "true" || literal == "false",
+            ScalarKind::Null => literal == "null",
+            _ => is_json_number(literal, false),
+        };
+        if !valid {
+            return Err(self.error("invalid literal"));
+        }
+        self.position += len;
+        self.scalars.push(Scalar {
+            key,
+            value: if kind == ScalarKind::Null {
+                String::new()
+            } else {
+                literal.to_string()
+            },
+            start,
+            end: self.position,
+            kind,
+        });
+        Ok(())
+    }
+
+    fn parse_string(&mut self) -> Result<String, InternalError> {
+        self.expect(b'"', "expected a string")?;
+        let mut value = String::new();
+        loop {
+            let rest = &self.text[self.position..];
+            let mut chars = rest.chars();
+            let c = chars
+                .next()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            match c {
+                '"' => {
+                    self.position += 1;
+                    return Ok(value);
+                }
+                '\\' => {
+                    let escaped = match chars.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\x08',
+                        Some('f') => '\x0c',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => {
+                            let (decoded, len) = self.parse_unicode_escape()?;
+                            value.push(decoded);
+                            self.position += len;
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    value.push(escaped);
+                    self.position += 2;
+                }
+                c if (c as u32) < 0x20 => return Err(self.error("control character in string")),
+                c => {
+                    value.push(c);
+                    self.position += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Decodes the '\uxxxx' escape at the current position, followed by the low surrogate of a
+    /// surrogate pair, returning the character and the length of the escape.
+    fn parse_unicode_escape(&self) -> Result<(char, usize), InternalError> {
+        let code_unit = |offset: usize| {
+            self.text
+                .get(self.position + offset..self.position + offset + 6)
+                .and_then(|escape| escape.strip_prefix("\\u"))
+                .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+        };
+        let high = code_unit(0).ok_or_else(|| self.error("invalid unicode escape"))?;
+        let mut units = vec![high];
+        if (0xD800..0xDC00).contains(&high) {
+            units.extend(code_unit(6));
+        }
+        let len = units.len() * 6;
+        match char::decode_utf16(units).next() {
+            Some(Ok(c)) => Ok((c, len)),
+            _ => Err(self.error("invalid unicode surrogate")),
+        }
+    }
+
+    fn parse_container(&mut self, key: String, kind: ContainerKind) -> Result<(), InternalError> {
+        let open = self.position;
+        let close_byte = if kind == ContainerKind::Object {
+            b'}'
+        } else {
+            b']'
+        };
+        self.position += 1;
+        self.skip_whitespace();
+        let separator = self.text[open + 1..self.position].to_string();
+        let mut last_value_end: Option<usize> = None;
+        let mut len: usize = 0;
+        if self.peek() != Some(close_byte) {
+            loop {
+                let start = self.position;
+                let child = match kind {
+                    ContainerKind::Object => {
+                        let name = self.parse_string()?;
+                        let colon_start = self.position;
+                        self.skip_whitespace();
+                        self.expect(b':', "expected ':'")?;
+                        self.skip_whitespace();
+                        self.colon.get_or_insert_with(|| {
+                            self.text[colon_start..self.position].to_string()
+                        });
+                        if key.is_empty() {
+                            name
+                        } else {
+                            format!("{}.{}", key, name)
+                        }
+                    }
+                    ContainerKind::Array => format!("{}[{}]", key, len),
+                };
+                if kind == ContainerKind::Object {
+                    self.members.push(Member {
+                        key: child.clone(),
+                        object: open,
+                        start,
+                        end: 0,
+                    });
+                    let member = self.members.len() - 1;
+                    self.parse_value(child)?;
+                    self.members[member].end = self.position;
+                } else {
+                    self.parse_value(child)?;
+                }
+                len += 1;
+                last_value_end = Some(self.position);
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b',') => {
+                        self.position += 1;
+                        self.skip_whitespace();
+                    }
+                    Some(b) if b == close_byte => break,
+                    _ if kind == ContainerKind::Object => {
+                        return Err(self.error("expected ',' or '}'"))
+                    }
+                    _ => return Err(self.error("expected ',' or ']'")),
+                }
+            }
+        }
+        let indent = self.line_indent(open);
+        if let Some(member_indent) = separator.rsplit_once('\n').map(|(_, i)| i) {
+            if self.step.is_none() && member_indent.len() > indent.len() && !key.is_empty() {
+                self.step = Some(member_indent[indent.len()..].to_string());
+            }
+        }
+        let container = Container {
+            kind,
+            open,
+            close: self.position,
+            last_value_end,
+            separator,
+            indent,
+        };
+        match self.containers.entry(key) {
+            Entry::Occupied(entry) => self.shadowed.push((entry.key().clone(), container)),
+            Entry::Vacant(entry) => {
+                entry.insert(container);
+            }
+        }
+        self.position += 1;
+        Ok(())
+    }
+}
+
+/// The byte offsets where the lines of `text` start.
+fn line_starts(text: &str) -> Vec<usize> {
+    let bytes = text.as_bytes();
+    let mut starts = vec![0];
+    for (i, byte) in bytes.iter().enumerate() {
+        if *byte == b'\n' || (*byte == b'\r' && bytes.get(i + 1) != Some(&b'\n')) {
+            starts.push(i + 1);
+        }
+    }
+    starts
+}
+
+/// A JSON document whose values are addressed by flattened keys: the keys of nested objects are
+/// joined with '.' and the elements of arrays are addressed as '[n]'. Only the text of the
+/// updated values is rewritten, and added properties are inserted as nested objects into the
+/// deepest existing object or array, following the indentation of the document.
+#[derive(Debug)]
+pub struct JsonDocument<'a> {
+    text: &'a str,
+    scalars: Vec<Scalar>,
+    /// Replacement text of the updated scalars
+    replacements: HashMap<usize, String>,
+    /// Objects and arrays by flattened key, the root having an empty key
+    containers: HashMap<String, Container>,
+    /// Containers whose key is the key of a container of `containers`, until duplicates are
+    /// resolved
+    shadowed: Vec<(String, Container)>,
+    members: Vec<Member>,
+    /// Byte ranges of the members removed as duplicates
+    removals: Vec<(usize, usize)>,
+    multiline: bool,
+    step: String,
+    colon: String,
+    additions: Vec<Property<'a>>,
+    /// Whether added properties are members of the root object named after their whole key
+    flat: bool,
+}
+
+impl<'a> JsonDocument<'a> {
+    /// Parses `text`, which is considered an empty object if blank.
+    pub fn parse(text: &'a str) -> Result<JsonDocument<'a>, InternalError> {
+        let mut parser = JsonParser {
+            text,
+            position: 0,
+            scalars: Vec::new(),
+            containers: HashMap::new(),
+            shadowed: Vec::new(),
+            members: Vec::new(),
+            colon: None,
+            step: None,
+        };
+        parser.skip_whitespace();
+        if parser.peek().is_some() {
+            parser.parse_value(String::new())?;
+            parser.skip_whitespace();
+            if parser.peek().is_some() {
+                return Err(parser.error("unexpected characters after the document"));
+            }
+        }
+        let multiline = parser
+            .containers
+            .get("")
+            .is_none_or(|root| root.separator.contains('\n'));
+        Ok(JsonDocument {
+            text,
+            scalars: parser.scalars,
+            replacements: HashMap::new(),
+            containers: parser.containers,
+            shadowed: parser.shadowed,
+            members: parser.members,
+            removals: Vec::new(),
+            multiline,
+            step: parser.step.unwrap_or(DEFAULT_INDENT_STEP.to_string()),
+            colon: parser.colon.unwrap_or(": ".to_string()),
+            additions: Vec::new(),
+            flat: false,
+        })
+    }
+
+    /// An empty object whose added properties are written as members named after their whole
+    /// key, such as '"a.b": 1', rather than nested into objects.
+    pub fn flat() -> JsonDocument<'static> {
+        JsonDocument {
+            flat: true,
+            ..JsonDocument::parse("").unwrap()
+        }
+    }
+
+    /// The segments addressing the added property `key`.
+    fn segments(&self, key: &str) -> Vec<Segment> {
+        if self.flat {
+            vec![Segment::Key(key.to_string())]
+        } else {
+            key_segments(key)
+        }
+    }
+
+    /// Edits inserting the added properties into their containers, as replaced byte ranges of the
+    /// text and their replacement.
+    fn addition_edits(&self) -> Vec<(usize, usize, String)> {
+        let layout = Layout {
+            multiline: self.multiline,
+            step: self.step.clone(),
+            colon: self.colon.clone(),
+        };
+        let scalar_keys: HashSet<&str> = self.scalars.iter().map(|s| s.key.as_str()).collect();
+        let mut trees: Vec<(Option<&Container>, Nodes)> = Vec::new();
+        for property in &self.additions {
+            let segments = self.segments(&property.key);
+            let attach = (0..segments.len()).rev().find_map(|n| {
+                self.containers
+                    .get(&segments_key(&segments[..n]))
+                    .filter(|c| c.kind == segments[n].container_kind())
+                    .map(|c| (n, c))
+            });
+            // only empty documents have no root to attach to, as `push` rejects the others
+            let (n, container) = match attach {
+                Some((n, container)) => (n, Some(container)),
+                None => (0, None),
+            };
+            // keys nested under an existing scalar or array cannot be expanded into objects
+            let remaining = if (n + 1..segments.len()).any(|k| {
+                let prefix = segments_key(&segments[..k]);
+                scalar_keys.contains(prefix.as_str()) || self.containers.contains_key(&prefix)
+            }) {
+                vec![Segment::Key(segments_key(&segments[n..]))]
+            } else {
+                segments[n..].to_vec()
+            };
+            let position = trees.iter().position(|(c, _)| match (c, container) {
+                (Some(c), Some(container)) => std::ptr::eq(*c, container),
+                (None, None) => true,
+                _ => false,
+            });
+            let index = position.unwrap_or_else(|| {
+                trees.push((container, Vec::new()));
+                trees.len() - 1
+            });
+            insert_node(&mut trees[index].1, &remaining, &property.value);
+        }
+        let mut edits: Vec<(usize, usize, String)> = Vec::new();
+        for (container, children) in trees {
+            match container {
+                None => {
+                    let rendered = layout.render(&Node::Children(children), "");
+                    edits.push((self.text.len(), self.text.len(), format!("{}\n", rendered)));
+                }
+                Some(container) => {
+                    let member_indent = match container.separator.rsplit_once('\n') {
+                        Some((_, indent)) => indent.to_string(),
+                        None => format!("{}{}", container.indent, layout.step),
+                    };
+                    let members = layout.render_members(&children, &member_indent);
+                    match container.last_value_end {
+                        Some(end) => {
+                            let separator = if container.separator.contains('\n') {
+                                container.separator.as_str()
+                            } else {
+                                " "
+                            };
+                            let inserted: String = members
+                                .iter()
+                                .map(|member| format!(",{}{}", separator, member))
+                                .collect();
+                            edits.push((end, end, inserted));
+                        }
+                        None => {
+                            let rendered = if layout.multiline {
+                                format!(
+                                    "\n{}{}\n{}",
+                                    member_indent,
+                                    members.join(format!(",\n{}", member_indent).as_str()),
+                                    container.indent
+                                )
+                            } else {
+                                members.join(", ")
+                            };
+                            edits.push((container.open + 1, container.close, rendered));
+                        }
+                    }
+                }
+            }
+        }
+        edits
+    }
+}
+
+impl<'a> Document<'a> for JsonDocument<'a> {
+    fn properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.scalars
+            .iter()
+            .enumerate()
+            .map(|(index, scalar)| {
+                (
+                    index,
+                    Property {
+                        key: Cow::Borrowed(scalar.key.as_str()),
+                        value: Cow::Borrowed(scalar.value.as_str()),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Applies `policy` to the members defined more than once in the same object, removing the
+    /// members which are not retained together with the comma separating them from the others.
+    fn resolve_duplicates(&mut self, policy: DuplicatePolicy) -> Result<(), InternalError> {
+        if policy == DuplicatePolicy::KeepAll {
+            return Ok(());
+        }
+        let mut objects: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (index, member) in self.members.iter().enumerate() {
+            objects.entry(member.object).or_default().push(index);
+        }
+        let mut objects: Vec<(usize, Vec<usize>)> = objects.into_iter().collect();
+        objects.sort_by_key(|(object, _)| *object);
+        let line_starts = line_starts(self.text);
+        let mut removals: Vec<(usize, usize)> = Vec::new();
+        for (_, members) in objects {
+            let definitions = members.iter().map(|index| {
+                let member = &self.members[*index];
+                let line_num = line_starts.partition_point(|start| *start <= member.start);
+                (*index, member.key.as_str(), line_num as i32)
+            });
+            let removed = removed_duplicates(definitions, policy)?;
+            let mut position: usize = 0;
+            while position < members.len() {
+                if !removed.contains(&members[position]) {
+                    position += 1;
+                    continue;
+                }
+                let first = position;
+                while position < members.len() && removed.contains(&members[position]) {
+                    position += 1;
+                }
+                let last = &self.members[members[position - 1]];
+                removals.push(match members.get(position) {
+                    // the next member takes the place of the removed ones
+                    Some(next) => (
+                        self.members[members[first]].start,
+                        self.members[*next].start,
+                    ),
+                    // the preceding member becomes the last one, one member being retained
+                    None => (self.members[members[first - 1]].end, last.end),
+                });
+            }
+        }
+        let is_removed = |offset: usize| {
+            removals
+                .iter()
+                .any(|(start, end)| (*start..*end).contains(&offset))
+        };
+        self.scalars.retain(|scalar| !is_removed(scalar.start));
+        self.containers
+            .retain(|_, container| !is_removed(container.open));
+        for (key, container) in self.shadowed.drain(..) {
+            if !is_removed(container.open) {
+                self.containers.entry(key).or_insert(container);
+            }
+        }
+        self.removals.extend(removals);
+        Ok(())
+    }
+
+    /// Replaces the value at `index`, keeping its type when `value` represents one of the same
+    /// type.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError> {
+        let scalar = &mut self.scalars[index];
+        self.replacements
+            .insert(index, typed_value(value, scalar.kind));
+        scalar.value = value.to_string();
+        Ok(())
+    }
+
+    /// Adds the property to the document, failing when its root is not a container of the kind
+    /// addressed by the first segment of the key, such as an array for 'a.b'.
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError> {
+        let kind = self.segments(&property.key)[0].container_kind();
+        let reason = match self.containers.get("") {
+            Some(root) if root.kind == kind => None,
+            Some(root) if root.kind == ContainerKind::Array => {
+                Some("the root of the document is an array")
+            }
+            Some(_) => Some("the root of the document is an object"),
+            None if self.scalars.is_empty() => None,
+            None => Some("the root of the document is a scalar"),
+        };
+        if let Some(reason) = reason {
+            return Err(InternalError::unrepresentable_property(
+                property.key.as_ref(),
+                Format::Json,
+                reason,
+            ));
+        }
+        self.additions.push(property);
+        Ok(())
+    }
+
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()> {
+        let mut edits = self.addition_edits();
+        for (start, end) in &self.removals {
+            edits.push((*start, *end, String::new()));
+        }
+        for (index, replacement) in &self.replacements {
+            let scalar = &self.scalars[*index];
+            edits.push((scalar.start, scalar.end, replacement.clone()));
+        }
+        edits.sort_by_key(|(start, end, _)| (*start, *end));
+        let mut rendered = String::with_capacity(self.text.len());
+        let mut position: usize = 0;
+        for (start, end, replacement) in edits {
+            rendered.push_str(&self.text[position..start]);
+            rendered.push_str(replacement.as_str());
+            position = end;
+        }
+        rendered.push_str(&self.text[position..]);
+        if rendered.trim().is_empty() && self.containers.is_empty() {
+            // a blank document stands for an empty object
+            rendered = "{}".to_string();
+        }
+        let mut lines = split_physical_lines(&rendered);
+        if lines.last() == Some(&"") {
+            lines.pop();
+        }
+        for line in lines {
+            output.write_all(line.as_bytes())?;
+            output.write_all(line_ending.as_str().as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod json_document_tests {
+    use super::*;
+
+    fn parse(s: &str) -> JsonDocument<'_> {
+        JsonDocument::parse(s).unwrap()
+    }
+
+    fn properties(document: &JsonDocument) -> Vec<(String, String)> {
+        Document::properties(document)
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect()
+    }
+
+    fn write(document: &JsonDocument) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        Document::write(document, &mut output, LineEnding::Lf).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_should_flatten_objects_and_arrays() {
+        let document = parse(
+            "{\"name\": \"app\", \"server\": {\"port\": 8080, \"hosts\": [\"a\", {\"b\": true}], \"ratio\": -1.5e3}, \"extra\": null, \"s\": \"\\u00e8\\n\\ud83d\\ude00\"}",
+        );
+
+        assert_eq!(
+            properties(&document),
+            pairs(&[
+                ("name", "app"),
+                ("server.port", "8080"),
+                ("server.hosts[0]", "a"),
+                ("server.hosts[1].b", "true"),
+                ("server.ratio", "-1.5e3"),
+                ("extra", ""),
+                ("s", "è\n😀"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_should_report_errors_with_position() {
+        for (input, line, column) in [
+            ("{\n  \"a\": 1,\n  \"b\" 2\n}", 3, 7),
+            ("{\"a\": tru}", 1, 7),
+            ("{\"a\": 01}", 1, 7),
+            ("{\"a\": 1} x", 1, 10),
+            ("[1, 2", 1, 6),
+        ] {
+            match JsonDocument::parse(input).unwrap_err() {
+                InternalError::ParseError {
+                    line_num, snippet, ..
+                } => assert_eq!(
+                    (line_num, snippet.unwrap().column),
+                    (line, column),
+                    "{}",
+                    input
+                ),
+                _ => panic!("error is not ParseError"),
+            }
+        }
+    }
+
+    #[test]
+    fn write_should_reproduce_input_exactly() {
+        let input = "{\n  \"a\" : [ 1,2 ],\n\t\"b\": {\"c\": \"\\/x\"}\n}\n";
+        let document = parse(input);
+
+        assert_eq!(write(&document), input);
+    }
+
+    #[test]
+    fn write_should_use_given_line_ending() {
+        let document = parse("{\r\n  \"a\": 1\r\n}\r\n");
+        let mut output: Vec<u8> = Vec::new();
+
+        Document::write(&document, &mut output, LineEnding::CrLf).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "{\r\n  \"a\": 1\r\n}\r\n"
+        );
+    }
+
+    #[test]
+    fn set_value_should_keep_type_when_possible() {
+        let mut document = parse(
+            "{\"port\": 8080, \"ratio\": 1.5, \"enabled\": false, \"name\": \"x\", \"other\": 1, \"empty\": null, \"q\": null}",
+        );
+
+        document.set_value(0, "9090").unwrap();
+        document.set_value(1, "2e3").unwrap();
+        document.set_value(2, "true").unwrap();
+        document.set_value(3, "say \"hi\"").unwrap();
+        document.set_value(4, "not a number").unwrap();
+        document.set_value(5, "42").unwrap();
+        document.set_value(6, "007").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "{\"port\": 9090, \"ratio\": 2e3, \"enabled\": true, \"name\": \"say \\\"hi\\\"\", \"other\": \"not a number\", \"empty\": 42, \"q\": \"007\"}\n"
+        );
+        assert_eq!(
+            properties(&document)[3],
+            ("name".to_string(), "say \"hi\"".to_string())
+        );
+    }
+
+    #[test]
+    fn push_should_insert_nested_objects_following_indentation() {
+        let mut document = parse(
+            "{\n    \"server\": {\n        \"port\": 8080,\n        \"tls\": {}\n    },\n    \"hosts\": [\"a\"]\n}\n",
+        );
+
+        document
+            .push(Property::new("server.tls.enabled", "true"))
+            .unwrap();
+        document
+            .push(Property::new("server.host", "localhost"))
+            .unwrap();
+        document
+            .push(Property::new("logging.level.root", "info"))
+            .unwrap();
+        document
+            .push(Property::new("logging.level.web", "debug"))
+            .unwrap();
+        document.push(Property::new("hosts[1]", "b")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "{\n    \"server\": {\n        \"port\": 8080,\n        \"tls\": {\n            \"enabled\": true\n        },\n        \"host\": \"localhost\"\n    },\n    \"hosts\": [\"a\", \"b\"],\n    \"logging\": {\n        \"level\": {\n            \"root\": \"info\",\n            \"web\": \"debug\"\n        }\n    }\n}\n"
+        );
+    }
+
+    #[test]
+    fn push_should_keep_compact_documents_on_one_line() {
+        let mut document = parse("{\"a\": 1, \"b\": {}}");
+
+        document.push(Property::new("b.c", "x")).unwrap();
+        document.push(Property::new("d.e", "2")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "{\"a\": 1, \"b\": {\"c\": \"x\"}, \"d\": {\"e\": 2}}\n"
+        );
+    }
+
+    #[test]
+    fn push_should_add_keys_nested_under_values_as_flat_keys() {
+        let mut document = parse("{\"a\": 1, \"b\": [1]}");
+
+        document.push(Property::new("a.b", "x")).unwrap();
+        document.push(Property::new("b.c", "y")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "{\"a\": 1, \"b\": [1], \"a.b\": \"x\", \"b.c\": \"y\"}\n"
+        );
+    }
+
+    #[test]
+    fn write_should_write_empty_object_for_blank_input() {
+        assert_eq!(write(&parse("")), "{}\n");
+        assert_eq!(write(&parse(" \n")), "{}\n");
+    }
+
+    #[test]
+    fn resolve_duplicates_should_remove_discarded_members() {
+        let input = "{\n  \"a\": 1,\n  \"b\": {\"c\": 2, \"c\": 3},\n  \"a\": {\"d\": 4}\n}\n";
+
+        let mut last_wins = parse(input);
+        last_wins
+            .resolve_duplicates(DuplicatePolicy::LastWins)
+            .unwrap();
+        let mut first_wins = parse(input);
+        first_wins
+            .resolve_duplicates(DuplicatePolicy::FirstWins)
+            .unwrap();
+        first_wins.push(Property::new("e", "5")).unwrap();
+
+        assert_eq!(
+            write(&last_wins),
+            "{\n  \"b\": {\"c\": 3},\n  \"a\": {\"d\": 4}\n}\n"
+        );
+        assert_eq!(properties(&last_wins), pairs(&[("b.c", "3"), ("a.d", "4")]));
+        assert_eq!(
+            write(&first_wins),
+            "{\n  \"a\": 1,\n  \"b\": {\"c\": 2},\n  \"e\": 5\n}\n"
+        );
+        assert_eq!(
+            parse(input)
+                .resolve_duplicates(DuplicatePolicy::Error)
+                .unwrap_err()
+                .to_string(),
+            "duplicate key 'a' at lines 2 and 4"
+        );
+    }
+
+    #[test]
+    fn push_should_reject_keys_of_another_kind_than_root() {
+        let mut array = parse("[1, 2]\n");
+        let mut scalar = parse("1\n");
+
+        assert_eq!(
+            array.push(Property::new("a", "x")).unwrap_err().to_string(),
+            "property 'a' cannot be represented in the json format: the root of the document is an array"
+        );
+        assert!(scalar.push(Property::new("a", "x")).is_err());
+        array.push(Property::new("[2]", "3")).unwrap();
+
+        assert_eq!(write(&array), "[1, 2, 3]\n");
+        assert_eq!(write(&scalar), "1\n");
+    }
+
+    #[test]
+    fn push_should_add_whole_keys_to_flat_documents() {
+        let mut document = JsonDocument::flat();
+
+        document.push(Property::new("a.b", "1")).unwrap();
+        document.push(Property::new("a.c[0]", "x")).unwrap();
+        document.push(Property::new("[0]", "y")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "{\n  \"a.b\": 1,\n  \"a.c[0]\": \"x\",\n  \"[0]\": \"y\"\n}\n"
+        );
+        assert_eq!(
+            properties(&parse(&write(&document))),
+            pairs(&[("a.b", "1"), ("a.c[0]", "x"), ("[0]", "y")])
+        );
+    }
+
+    #[test]
+    fn push_should_create_object_for_empty_input() {
+        let mut document = parse("");
+
+        document.push(Property::new("a.b", "1")).unwrap();
+        document.push(Property::new("c", "x")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "{\n  \"a\": {\n    \"b\": 1\n  },\n  \"c\": \"x\"\n}\n"
+        );
+    }
+}
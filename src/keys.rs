@@ -0,0 +1,126 @@
+/// A segment of a flattened key, which addresses the values of nested documents through property
+/// keys: nested keys are joined with '.' and sequence items are addressed as '[n]'.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Splits a flattened key such as 'a.b[0].c' into its segments. Keys starting with an index,
+/// such as '[0].a', address the items of a root sequence.
+pub fn key_segments(key: &str) -> Vec<Segment> {
+    let mut segments: Vec<Segment> = Vec::new();
+    for part in key.split('.') {
+        let indexed = part
+            .find('[')
+            .filter(|start| *start > 0 || segments.is_empty())
+            .and_then(|start| parse_indices(&part[start..]).map(|indices| (start, indices)));
+        match indexed {
+            Some((0, indices)) => segments.extend(indices.into_iter().map(Segment::Index)),
+            Some((start, indices)) => {
+                segments.push(Segment::Key(part[..start].to_string()));
+                segments.extend(indices.into_iter().map(Segment::Index));
+            }
+            None => segments.push(Segment::Key(part.to_string())),
+        }
+    }
+    segments
+}
+
+fn parse_indices(text: &str) -> Option<Vec<usize>> {
+    let mut indices: Vec<usize> = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (index, after) = rest.strip_prefix('[')?.split_once(']')?;
+        indices.push(index.parse().ok()?);
+        rest = after;
+    }
+    Some(indices)
+}
+
+/// Joins segments back into a flattened key.
+pub fn segments_key(segments: &[Segment]) -> String {
+    let mut key = String::new();
+    for segment in segments {
+        match segment {
+            Segment::Key(name) if key.is_empty() => key.push_str(name),
+            Segment::Key(name) => {
+                key.push('.');
+                key.push_str(name);
+            }
+            Segment::Index(index) => key.push_str(format!("[{}]", index).as_str()),
+        }
+    }
+    key
+}
+
+/// New content to insert into a nested document, built from the keys of added properties.
+pub enum Node {
+    Value(String),
+    Children(Nodes),
+}
+
+pub type Nodes = Vec<(Segment, Node)>;
+
+/// Inserts `value` at the path made of `segments`, merging it with the nodes already present.
+pub fn insert_node(children: &mut Nodes, segments: &[Segment], value: &str) {
+    let (first, rest) = segments.split_first().unwrap();
+    if rest.is_empty() {
+        children.push((first.clone(), Node::Value(value.to_string())));
+        return;
+    }
+    let position = children
+        .iter()
+        .position(|(segment, node)| segment == first && matches!(node, Node::Children(_)));
+    let index = position.unwrap_or_else(|| {
+        children.push((first.clone(), Node::Children(Vec::new())));
+        children.len() - 1
+    });
+    if let Node::Children(grandchildren) = &mut children[index].1 {
+        insert_node(grandchildren, rest, value);
+    }
+}
+
+#[cfg(test)]
+mod keys_tests {
+    use super::*;
+
+    #[test]
+    fn key_segments_should_split_keys_and_indices() {
+        assert_eq!(
+            key_segments("a.b[0][1].c"),
+            vec![
+                Segment::Key("a".to_string()),
+                Segment::Key("b".to_string()),
+                Segment::Index(0),
+                Segment::Index(1),
+                Segment::Key("c".to_string()),
+            ]
+        );
+        assert_eq!(key_segments("a[x]"), vec![Segment::Key("a[x]".to_string())]);
+        assert_eq!(
+            key_segments("[1].a"),
+            vec![Segment::Index(1), Segment::Key("a".to_string())]
+        );
+        assert_eq!(
+            key_segments("a.[1]"),
+            vec![
+                Segment::Key("a".to_string()),
+                Segment::Key("[1]".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn segments_key_should_join_segments() {
+        assert_eq!(
+            segments_key(&[
+                Segment::Key("a".to_string()),
+                Segment::Index(0),
+                Segment::Key("b".to_string())
+            ]),
+            "a[0].b"
+        );
+        assert_eq!(segments_key(&[Segment::Index(1)]), "[1]");
+    }
+}
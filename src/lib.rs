@@ -0,0 +1,23 @@
+#[cfg(test)]
+#[macro_use]
+extern crate maplit;
+pub mod convert;
+pub mod document;
+pub mod dotenv_document;
+pub mod encoding;
+pub mod format_detection;
+pub mod hocon_document;
+pub mod ini_document;
+pub mod json_document;
+pub mod keys;
+pub mod line_ending;
+pub mod manifest;
+pub mod model;
+pub mod overriding;
+pub mod properties_document;
+pub mod properties_parser;
+#[cfg(test)]
+mod test_utils;
+pub mod toml_document;
+pub mod xml_document;
+pub mod yaml_document;
@@ -0,0 +1,91 @@
+use crate::model::LineEnding;
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Detects the line ending style of `text` from its first line terminator, defaulting to '\n'
+/// when the text contains none.
+pub fn detect_line_ending(text: &str) -> LineEnding {
+    match text.find(['\n', '\r']) {
+        Some(index) if text[index..].starts_with("\r\n") => LineEnding::CrLf,
+        _ => LineEnding::Lf,
+    }
+}
+
+/// Splits `text` on '\n', '\r\n' and '\r', as `java.util.Properties.load` does. Terminators are
+/// not included in the lines and the text following the last terminator is always produced, even
+/// when empty.
+pub fn split_physical_lines(text: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = Vec::new();
+    let mut rest = text;
+    while let Some(end) = rest.find(['\n', '\r']) {
+        lines.push(&rest[..end]);
+        let terminator_len = if rest[end..].starts_with("\r\n") {
+            2
+        } else {
+            1
+        };
+        rest = &rest[end + terminator_len..];
+    }
+    lines.push(rest);
+    lines
+}
+
+#[cfg(test)]
+mod detect_line_ending_tests {
+    use super::*;
+
+    #[test]
+    fn should_detect_lf() {
+        assert_eq!(detect_line_ending("a=b\nc=d\r\n"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn should_detect_crlf() {
+        assert_eq!(detect_line_ending("a=b\r\nc=d\n"), LineEnding::CrLf);
+    }
+
+    #[test]
+    fn should_default_to_lf_without_terminators() {
+        assert_eq!(detect_line_ending("a=b"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn should_treat_lone_carriage_return_as_lf() {
+        assert_eq!(detect_line_ending("a=b\rc=d"), LineEnding::Lf);
+    }
+}
+
+#[cfg(test)]
+mod split_physical_lines_tests {
+    use super::*;
+
+    #[test]
+    fn should_split_on_all_terminators() {
+        assert_eq!(
+            split_physical_lines("a\nb\r\nc\rd"),
+            vec!["a", "b", "c", "d"]
+        );
+    }
+
+    #[test]
+    fn should_produce_final_empty_line() {
+        assert_eq!(split_physical_lines("a\r\nb\r\n"), vec!["a", "b", ""]);
+    }
+
+    #[test]
+    fn should_keep_empty_lines() {
+        assert_eq!(split_physical_lines("a\r\n\r\n\nb"), vec!["a", "", "", "b"]);
+    }
+
+    #[test]
+    fn should_produce_one_empty_line_for_empty_text() {
+        assert_eq!(split_physical_lines(""), vec![""]);
+    }
+}
@@ -1,20 +1,17 @@
-#[macro_use]
-extern crate maplit;
-mod model;
-mod overriding;
-mod properties_parser;
-mod test_utils;
-
-use crate::model::InternalError;
-use crate::overriding::{
-    CustomCaseSensitiveStyleOverrider, Environment, Overrider, SpringStyleOverrider,
-};
-use crate::properties_parser::{parse_line, Line};
 use clap::Parser;
-use model::Args;
+use properties_builder::convert::convert;
+use properties_builder::document::parse_document;
+use properties_builder::format_detection::detect_format;
+use properties_builder::manifest::{default_data_key, write_manifest};
+use properties_builder::model::{Args, InternalError, OverrideStyle, Property};
+use properties_builder::overriding::{
+    CustomCaseSensitiveStyleOverrider, DotNetStyleOverrider, Environment, HoconStyleOverrider,
+    KafkaStyleOverrider, MicroProfileStyleOverrider, Overrider, SpringStyleOverrider,
+};
+use properties_builder::{encoding, line_ending};
 use std::collections::HashSet;
 use std::fs::File;
-use std::io::{stdin, stdout, BufRead, BufReader, BufWriter, Write};
+use std::io::{stdin, stdout, BufRead, BufReader, BufWriter, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::{fs, path};
@@ -22,7 +19,7 @@ use std::{fs, path};
 fn main_exec() -> Result<(), InternalError> {
     let configuration = Args::parse().validate_and_convert()?;
     let empty_buffer: &[u8] = &[];
-    let input: Box<dyn BufRead> = if configuration.empty_input {
+    let mut input: Box<dyn BufRead> = if configuration.empty_input {
         Box::new(BufReader::new(empty_buffer))
     } else if configuration.file.is_none() {
         Box::new(BufReader::new(stdin()))
@@ -48,12 +45,29 @@ fn main_exec() -> Result<(), InternalError> {
             } else {
                 Path::new(configuration.output_file.clone().unwrap().as_str()).to_path_buf()
             };
-            let f = File::options().create(true).write(true).open(&path)?;
+            let f = File::options()
+                .create(true)
+                .write(true)
+                .truncate(true)
+                .open(&path)?;
             (Box::new(BufWriter::new(f)), Some(path))
         };
     let env: Environment = Environment::new(&std::env::vars().collect());
     let overrider: Box<dyn Overrider> = if configuration.spring {
         Box::new(SpringStyleOverrider::new(env))
+    } else if let Some(style) = configuration.style {
+        match style {
+            OverrideStyle::Kafka => {
+                Box::new(KafkaStyleOverrider::new(env, &configuration.exclusions))
+            }
+            OverrideStyle::Hocon => Box::new(HoconStyleOverrider::new(env)),
+            OverrideStyle::MicroProfile => Box::new(MicroProfileStyleOverrider::new(env)),
+            OverrideStyle::DotNet => Box::new(DotNetStyleOverrider::new(
+                env,
+                configuration.prefix.as_deref().unwrap_or_default(),
+                &configuration.hierarchy_delimiter,
+            )),
+        }
     } else {
         Box::new(CustomCaseSensitiveStyleOverrider::new(
             configuration.replacement_map,
@@ -62,29 +76,93 @@ fn main_exec() -> Result<(), InternalError> {
     };
 
     let mut defined_properties: HashSet<String> = HashSet::new();
-
-    for (line_num, line_result) in input.lines().enumerate() {
-        let line = line_result?;
-        let parse_result = parse_line(line.as_str(), (line_num + 1) as i32)?;
-        match parse_result {
-            Line::Ignorable(line) => writeln!(output, "{}", line)?,
-            Line::Prop(property) => {
-                let overridden = overrider.resolve_substitution(
-                    property.key.as_str(),
-                    Some(configuration.prefix.as_str()),
-                );
-                if let Some(overridden_value) = overridden {
-                    writeln!(output, "{}={}", property.key, overridden_value)?;
-                } else {
-                    writeln!(output, "{}={}", property.key, property.value)?;
-                }
-                defined_properties.replace(property.key);
+    let mut input_bytes: Vec<u8> = Vec::new();
+    input.read_to_end(&mut input_bytes)?;
+    let input_text = encoding::decode(&input_bytes, configuration.input_encoding)?;
+    let line_ending = configuration
+        .line_ending
+        .unwrap_or_else(|| line_ending::detect_line_ending(&input_text));
+    let format = configuration
+        .format
+        .unwrap_or_else(|| detect_format(&input_text));
+    let output_format = configuration.output_format.unwrap_or(format);
+    let mut document = parse_document(&input_text, format)?;
+    document.resolve_duplicates(configuration.duplicates)?;
+    let prefix = configuration.prefix.as_deref();
+    let mut overrides: Vec<(usize, String)> = Vec::new();
+    for (index, property) in document.properties() {
+        let overridden =
+            prefix.and_then(|prefix| overrider.resolve_substitution(&property.key, Some(prefix)));
+        if let Some(overridden_value) = overridden {
+            overrides.push((index, overridden_value.to_string()));
+        }
+        defined_properties.replace(overrider.canonical_key(&property.key));
+    }
+    for (index, overridden_value) in overrides {
+        document.set_value(index, overridden_value.as_str())?;
+    }
+    if configuration.activate_commented {
+        let mut activations: Vec<(usize, Property)> = Vec::new();
+        for (index, property) in document.commented_properties() {
+            if defined_properties.contains(&overrider.canonical_key(&property.key)) {
+                continue;
+            }
+            let overridden = prefix
+                .and_then(|prefix| overrider.resolve_substitution(&property.key, Some(prefix)));
+            if let Some(overridden_value) = overridden {
+                activations.push((
+                    index,
+                    Property::new(property.key.as_ref(), overridden_value),
+                ));
+                defined_properties.replace(overrider.canonical_key(&property.key));
             }
         }
+        for (index, property) in activations {
+            document.activate(index, property)?;
+        }
+    }
+    let additions = prefix
+        .map(|prefix| overrider.generate_additions(prefix))
+        .unwrap_or_default();
+    for property in additions {
+        if !defined_properties.contains(&overrider.canonical_key(&property.key)) {
+            document.push(property)?;
+        }
+    }
+    let mut rendered: Vec<u8> = Vec::new();
+    if output_format == format {
+        document.write(&mut rendered, line_ending)?;
+    } else {
+        if let Some((_, property)) = document.unresolved_properties().first() {
+            return Err(InternalError::unrepresentable_property(
+                property.key.as_ref(),
+                output_format,
+                format!(
+                    "its value '{}' is a substitution which the conversion does not resolve",
+                    property.value
+                )
+                .as_str(),
+            ));
+        }
+        let properties = document
+            .properties()
+            .into_iter()
+            .map(|(_, property)| property.into_owned())
+            .collect();
+        convert(properties, output_format)?.write(&mut rendered, line_ending)?;
     }
-    for property in overrider.generate_additions(configuration.prefix.as_str()) {
-        if !defined_properties.contains(property.key.as_str()) {
-            writeln!(output, "{}={}", property.key, property.value)?;
+    let content = encoding::encode(
+        String::from_utf8_lossy(&rendered).as_ref(),
+        configuration.output_encoding,
+        output_format,
+    )?;
+    match &configuration.manifest {
+        None => output.write_all(&content)?,
+        Some(manifest) => {
+            let data_key = manifest.data_key.clone().unwrap_or_else(|| {
+                default_data_key(configuration.file.as_deref(), format, output_format)
+            });
+            write_manifest(&mut output, manifest, &data_key, &content, line_ending)?;
         }
     }
     output.flush()?;
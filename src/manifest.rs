@@ -0,0 +1,229 @@
+use crate::json_document::quote;
+use crate::line_ending::split_physical_lines;
+use crate::model::{Format, LineEnding, Manifest, ManifestKind};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn extension(format: Format) -> &'static str {
+    match format {
+        Format::Properties => "properties",
+        Format::Yaml => "yaml",
+        Format::Toml => "toml",
+        Format::Json => "json",
+        Format::Dotenv => "env",
+        Format::Xml => "xml",
+        Format::Ini => "ini",
+        Format::Hocon => "conf",
+    }
+}
+
+/// The key holding the output in the data of a manifest when none is given: the name of the input
+/// file when the output has its format, otherwise 'application' with the extension of the output
+/// format.
+pub fn default_data_key(file: Option<&str>, format: Format, output_format: Format) -> String {
+    file.filter(|_| format == output_format)
+        .and_then(|file| Path::new(file).file_name()?.to_str())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| format!("application.{}", extension(output_format)))
+}
+
+/// Renders `text` as the lines of a literal block scalar nested under a key indented by 2
+/// spaces, or `None` when it contains characters which a block scalar cannot retain, such as
+/// carriage returns.
+fn block_scalar(text: &str) -> Option<Vec<String>> {
+    if text.is_empty() || text.contains(|c: char| c.is_control() && c != '\n' && c != '\t') {
+        return None;
+    }
+    let mut lines = split_physical_lines(text);
+    let chomping = if !text.ends_with('\n') {
+        "-"
+    } else if text.ends_with("\n\n") || text == "\n" {
+        "+"
+    } else {
+        ""
+    };
+    if text.ends_with('\n') {
+        lines.pop();
+    }
+    let leading_space = lines
+        .iter()
+        .find(|line| !line.is_empty())
+        .is_some_and(|line| line.starts_with([' ', '\t']));
+    let mut block = vec![format!(
+        "|{}{}",
+        if leading_space { "2" } else { "" },
+        chomping
+    )];
+    block.extend(lines.iter().map(|line| {
+        if line.is_empty() {
+            String::new()
+        } else {
+            format!("    {}", line)
+        }
+    }));
+    Some(block)
+}
+
+/// Writes a Kubernetes manifest of the kind of `manifest` holding `content` under `data_key`.
+/// ConfigMaps hold text as a literal block scalar, or as a quoted string when it cannot be
+/// represented as one, and content which is not valid UTF-8 as base64 binary data.
+pub fn write_manifest(
+    output: &mut dyn Write,
+    manifest: &Manifest,
+    data_key: &str,
+    content: &[u8],
+    line_ending: LineEnding,
+) -> io::Result<()> {
+    let mut lines: Vec<String> = vec!["apiVersion: v1".to_string()];
+    lines.push(match manifest.kind {
+        ManifestKind::ConfigMap => "kind: ConfigMap".to_string(),
+        ManifestKind::Secret => "kind: Secret".to_string(),
+    });
+    lines.push("metadata:".to_string());
+    lines.push(format!("  name: {}", manifest.name));
+    if let Some(namespace) = &manifest.namespace {
+        lines.push(format!("  namespace: {}", namespace));
+    }
+    match (manifest.kind, std::str::from_utf8(content)) {
+        (ManifestKind::ConfigMap, Ok(text)) => {
+            lines.push("data:".to_string());
+            match block_scalar(text) {
+                Some(block) => {
+                    lines.push(format!("  {}: {}", data_key, block[0]));
+                    lines.extend(block.into_iter().skip(1));
+                }
+                None => lines.push(format!("  {}: {}", data_key, quote(text))),
+            }
+        }
+        (ManifestKind::ConfigMap, Err(_)) => {
+            lines.push("binaryData:".to_string());
+            lines.push(format!("  {}: {}", data_key, base64(content)));
+        }
+        (ManifestKind::Secret, _) => {
+            lines.push("type: Opaque".to_string());
+            lines.push("data:".to_string());
+            lines.push(format!("  {}: {}", data_key, base64(content)));
+        }
+    }
+    for line in lines {
+        output.write_all(line.as_bytes())?;
+        output.write_all(line_ending.as_str().as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod manifest_tests {
+    use super::*;
+
+    fn manifest(kind: ManifestKind, namespace: Option<&str>) -> Manifest {
+        Manifest {
+            kind,
+            name: "app-config".to_string(),
+            namespace: namespace.map(|namespace| namespace.to_string()),
+            data_key: None,
+        }
+    }
+
+    fn written(manifest: &Manifest, content: &[u8]) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        write_manifest(
+            &mut output,
+            manifest,
+            "application.properties",
+            content,
+            LineEnding::Lf,
+        )
+        .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn base64_should_pad_partial_groups() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"a=1\n\xff"), "YT0xCv8=");
+    }
+
+    #[test]
+    fn default_data_key_should_use_input_file_name_when_format_is_retained() {
+        assert_eq!(
+            default_data_key(Some("conf/app.yml"), Format::Yaml, Format::Yaml),
+            "app.yml"
+        );
+        assert_eq!(
+            default_data_key(Some("conf/app.yml"), Format::Yaml, Format::Dotenv),
+            "application.env"
+        );
+        assert_eq!(
+            default_data_key(None, Format::Properties, Format::Properties),
+            "application.properties"
+        );
+    }
+
+    #[test]
+    fn write_manifest_should_write_config_map_with_block_scalar() {
+        assert_eq!(
+            written(
+                &manifest(ManifestKind::ConfigMap, Some("prod")),
+                b"# c\na=1\n\nb=2\n"
+            ),
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app-config\n  namespace: prod\ndata:\n  application.properties: |\n    # c\n    a=1\n\n    b=2\n"
+        );
+    }
+
+    #[test]
+    fn write_manifest_should_keep_leading_spaces_and_trailing_lines() {
+        assert_eq!(
+            written(&manifest(ManifestKind::ConfigMap, None), b"  a=1\nb=2"),
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app-config\ndata:\n  application.properties: |2-\n      a=1\n    b=2\n"
+        );
+        assert_eq!(
+            written(&manifest(ManifestKind::ConfigMap, None), b"a=1\n\n"),
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app-config\ndata:\n  application.properties: |+\n    a=1\n\n"
+        );
+    }
+
+    #[test]
+    fn write_manifest_should_quote_text_which_block_scalars_cannot_hold() {
+        assert_eq!(
+            written(&manifest(ManifestKind::ConfigMap, None), b"a=1\r\n"),
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app-config\ndata:\n  application.properties: \"a=1\\r\\n\"\n"
+        );
+    }
+
+    #[test]
+    fn write_manifest_should_write_binary_data_and_secrets_in_base64() {
+        assert_eq!(
+            written(&manifest(ManifestKind::ConfigMap, None), b"a=\xe9\n"),
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app-config\nbinaryData:\n  application.properties: YT3pCg==\n"
+        );
+        assert_eq!(
+            written(&manifest(ManifestKind::Secret, Some("prod")), b"a=1\n"),
+            "apiVersion: v1\nkind: Secret\nmetadata:\n  name: app-config\n  namespace: prod\ntype: Opaque\ndata:\n  application.properties: YT0xCg==\n"
+        );
+    }
+}
@@ -1,29 +1,76 @@
-use clap::Parser;
+use crate::format_detection::detect_format_of_path;
+use crate::line_ending::split_physical_lines;
+use clap::{Parser, ValueEnum};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 use std::io;
 
-#[derive(Debug, PartialEq)]
-pub struct Property {
-    pub key: String,
-    pub value: String,
+/// A property whose key and value are either borrowed from the parsed text or owned.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Property<'a> {
+    pub key: Cow<'a, str>,
+    pub value: Cow<'a, str>,
 }
 
-impl Property {
-    pub fn new<S: AsRef<str>>(key: S, value: S) -> Property {
+impl Property<'_> {
+    pub fn new<S: AsRef<str>>(key: S, value: S) -> Property<'static> {
         Property {
-            key: key.as_ref().to_string(),
-            value: value.as_ref().to_string(),
+            key: Cow::Owned(key.as_ref().to_string()),
+            value: Cow::Owned(value.as_ref().to_string()),
         }
     }
+
+    pub fn into_owned(self) -> Property<'static> {
+        Property {
+            key: Cow::Owned(self.key.into_owned()),
+            value: Cow::Owned(self.value.into_owned()),
+        }
+    }
+}
+
+/// The source line an error refers to, with the 1-based column of the offending character.
+#[derive(Debug, PartialEq)]
+pub struct Snippet {
+    pub column: i32,
+    pub line: String,
+}
+
+impl Display for Snippet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // reproduce tabs in the marker line so that the caret stays aligned with the source
+        let padding: String = self
+            .line
+            .chars()
+            .take((self.column - 1) as usize)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        f.write_str(format!("{}\n{}^", self.line, padding).as_str())
+    }
 }
 
 #[derive(Debug)]
 pub enum InternalError {
-    ParseError { line_num: i32, message: String },
+    ParseError {
+        line_num: i32,
+        message: String,
+        snippet: Option<Snippet>,
+    },
+    ParseErrors(Vec<InternalError>),
+    DuplicateKey {
+        key: String,
+        first_line_num: i32,
+        second_line_num: i32,
+    },
     ArgumentValidationErrors(Vec<String>),
     FileAccessError(io::Error),
+    EncodingError(String),
+    UnrepresentableProperty {
+        key: String,
+        format: Format,
+        reason: String,
+    },
 }
 
 impl InternalError {
@@ -31,16 +78,101 @@ impl InternalError {
         InternalError::ParseError {
             line_num,
             message: message.as_ref().to_string(),
+            snippet: None,
         }
     }
+
+    pub fn parse_error_at<S: AsRef<str>>(
+        line_num: i32,
+        column: i32,
+        line: S,
+        message: S,
+    ) -> InternalError {
+        InternalError::ParseError {
+            line_num,
+            message: message.as_ref().to_string(),
+            snippet: Some(Snippet {
+                column,
+                line: line.as_ref().to_string(),
+            }),
+        }
+    }
+
+    pub fn unrepresentable_property<S: AsRef<str>>(
+        key: S,
+        format: Format,
+        reason: S,
+    ) -> InternalError {
+        InternalError::UnrepresentableProperty {
+            key: key.as_ref().to_string(),
+            format,
+            reason: reason.as_ref().to_string(),
+        }
+    }
+
+    /// Builds a parse error pointing at the character at byte `offset` of `text`.
+    pub fn parse_error_at_offset<S: AsRef<str>>(
+        text: &str,
+        offset: usize,
+        message: S,
+    ) -> InternalError {
+        let offset = offset.min(text.len());
+        let preceding_lines = split_physical_lines(&text[..offset]);
+        let line_prefix = preceding_lines.last().unwrap();
+        let line_start = offset - line_prefix.len();
+        let line_end = text[offset..]
+            .find(['\n', '\r'])
+            .map(|i| offset + i)
+            .unwrap_or(text.len());
+        InternalError::parse_error_at(
+            preceding_lines.len() as i32,
+            line_prefix.chars().count() as i32 + 1,
+            &text[line_start..line_end],
+            message.as_ref(),
+        )
+    }
 }
 
 impl Display for InternalError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            InternalError::ParseError { line_num, message } => f.write_str(
+            InternalError::ParseError {
+                line_num,
+                message,
+                snippet: None,
+            } => f.write_str(
                 format!("cannot parse property at line {}: {}", line_num, message).as_str(),
             ),
+            InternalError::ParseError {
+                line_num,
+                message,
+                snippet: Some(snippet),
+            } => f.write_str(
+                format!(
+                    "cannot parse property at line {}, column {}: {}\n{}",
+                    line_num, snippet.column, message, snippet
+                )
+                .as_str(),
+            ),
+            InternalError::ParseErrors(errors) => f.write_str(
+                errors
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+                    .as_str(),
+            ),
+            InternalError::DuplicateKey {
+                key,
+                first_line_num,
+                second_line_num,
+            } => f.write_str(
+                format!(
+                    "duplicate key '{}' at lines {} and {}",
+                    key, first_line_num, second_line_num
+                )
+                .as_str(),
+            ),
             InternalError::ArgumentValidationErrors(messages) => f.write_str(
                 format!(
                     "invalid arguments:\n{}",
@@ -55,6 +187,22 @@ impl Display for InternalError {
             InternalError::FileAccessError(io_error) => {
                 f.write_str(format!("file access error: {}", io_error).as_str())
             }
+            InternalError::EncodingError(message) => {
+                f.write_str(format!("encoding error: {}", message).as_str())
+            }
+            InternalError::UnrepresentableProperty {
+                key,
+                format,
+                reason,
+            } => f.write_str(
+                format!(
+                    "property '{}' cannot be represented in the {} format: {}",
+                    key,
+                    format.to_possible_value().unwrap().get_name(),
+                    reason
+                )
+                .as_str(),
+            ),
         }
     }
 }
@@ -101,19 +249,24 @@ mod property_tests {
 mod error_tests {
     #[cfg(test)]
     mod parse_error_tests {
-        use crate::model::InternalError;
         use crate::model::InternalError::ParseError;
+        use crate::model::{InternalError, Snippet};
 
         #[test]
         fn parse_error_should_product_the_expected_error() {
             let parse_error = InternalError::parse_error(42, "foobar");
 
             match parse_error {
-                ParseError { line_num, message } => {
+                ParseError {
+                    line_num,
+                    message,
+                    snippet,
+                } => {
                     assert_eq!(line_num, 42);
                     assert_eq!(message, "foobar");
+                    assert_eq!(snippet, None);
                 }
-                _ => assert!(false),
+                _ => panic!("error is not ParseError"),
             }
         }
         #[test]
@@ -125,6 +278,92 @@ mod error_tests {
             assert_eq!(result, "cannot parse property at line 45: message");
         }
 
+        #[test]
+        fn parse_error_at_should_produce_the_expected_error() {
+            let parse_error = InternalError::parse_error_at(42, 3, "a=b", "foobar");
+
+            match parse_error {
+                ParseError {
+                    line_num,
+                    message,
+                    snippet,
+                } => {
+                    assert_eq!(line_num, 42);
+                    assert_eq!(message, "foobar");
+                    assert_eq!(
+                        snippet,
+                        Some(Snippet {
+                            column: 3,
+                            line: "a=b".to_string()
+                        })
+                    );
+                }
+                _ => panic!("error is not ParseError"),
+            }
+        }
+
+        #[test]
+        fn fmt_should_produce_the_expected_error_for_parse_error_with_snippet() {
+            let error = InternalError::parse_error_at(45, 4, "key=\\u12", "message");
+
+            let result = format!("{}", &error);
+
+            assert_eq!(
+                result,
+                "cannot parse property at line 45, column 4: message\nkey=\\u12\n   ^"
+            );
+        }
+
+        #[test]
+        fn fmt_should_align_caret_with_tabs() {
+            let error = InternalError::parse_error_at(45, 4, "\tk\tv", "message");
+
+            let result = format!("{}", &error);
+
+            assert_eq!(
+                result,
+                "cannot parse property at line 45, column 4: message\n\tk\tv\n\t \t^"
+            );
+        }
+
+        #[test]
+        fn parse_error_at_offset_should_locate_line_and_column() {
+            let error = InternalError::parse_error_at_offset("a=1\r\nbc=\\u1\nd", 8, "message");
+
+            assert_eq!(
+                error.to_string(),
+                "cannot parse property at line 2, column 4: message\nbc=\\u1\n   ^"
+            );
+        }
+
+        #[test]
+        fn fmt_should_produce_all_errors_for_parse_errors() {
+            let error = InternalError::ParseErrors(vec![
+                InternalError::parse_error(1, "first"),
+                InternalError::parse_error_at(3, 1, "x", "second"),
+            ]);
+
+            let result = format!("{}", &error);
+
+            assert_eq!(
+                result,
+                "cannot parse property at line 1: first\ncannot parse property at line 3, column 1: second\nx\n^"
+            );
+        }
+
+        #[test]
+        fn fmt_should_produce_the_expected_error_for_duplicate_key() {
+            let error = InternalError::DuplicateKey {
+                key: "foo".to_string(),
+                first_line_num: 3,
+                second_line_num: 7,
+            };
+
+            let result = format!("{}", &error);
+
+            assert_eq!(result, "duplicate key 'foo' at lines 3 and 7");
+        }
+
         #[test]
         fn fmt_should_produce_the_expected_error_for_argument_validation_error() {
             let error =
@@ -137,10 +376,14 @@ mod error_tests {
 
         fn assert_parse_error_equal(actual: &InternalError, expected: &InternalError) {
             match actual {
-                ParseError { line_num, message } => {
+                ParseError {
+                    line_num, message, ..
+                } => {
                     let (actual_line_num, actual_message) = (line_num, message);
                     match expected {
-                        ParseError { line_num, message } => {
+                        ParseError {
+                            line_num, message, ..
+                        } => {
                             let (expected_line_num, expected_message) = (line_num, message);
                             assert_eq!(actual_line_num, expected_line_num);
                             assert_eq!(actual_message, expected_message);
@@ -154,10 +397,14 @@ mod error_tests {
 
         fn assert_parse_error_not_equal(actual: &InternalError, expected: &InternalError) {
             match actual {
-                ParseError { line_num, message } => {
+                ParseError {
+                    line_num, message, ..
+                } => {
                     let (actual_line_num, actual_message) = (line_num, message);
                     match expected {
-                        ParseError { line_num, message } => {
+                        ParseError {
+                            line_num, message, ..
+                        } => {
                             let (expected_line_num, expected_message) = (line_num, message);
                             assert_ne!(
                                 (actual_line_num, actual_message),
@@ -189,21 +436,110 @@ mod error_tests {
     }
 }
 
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DuplicatePolicy {
+    /// Keep every definition of a key
+    KeepAll,
+    /// Keep only the last definition of a key, as `java.util.Properties` does
+    LastWins,
+    /// Keep only the first definition of a key
+    FirstWins,
+    /// Fail when a key is defined more than once
+    Error,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    /// UTF-8, with an optional byte order mark
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+    /// ISO-8859-1 (Latin-1), the historical encoding of properties files
+    #[value(name = "iso-8859-1", alias = "latin1")]
+    Iso88591,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum LineEnding {
+    /// Unix line endings ('\n')
+    Lf,
+    /// Windows line endings ('\r\n')
+    #[value(name = "crlf")]
+    CrLf,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Java properties
+    Properties,
+    /// YAML, with nested keys flattened into dotted keys and list items into '[n]' indices
+    Yaml,
+    /// TOML, with tables and dotted keys flattened into dotted keys and arrays into '[n]' indices
+    Toml,
+    /// JSON, with nested objects flattened into dotted keys and arrays into '[n]' indices
+    Json,
+    /// dotenv files of 'KEY=value' lines, as read by docker-compose
+    Dotenv,
+    /// Java properties in the XML form of `Properties.loadFromXML`
+    Xml,
+    /// INI, with the keys of sections prefixed by the section name as in 'section.key'
+    Ini,
+    /// HOCON, with objects and path expressions flattened into dotted keys and arrays into '[n]' indices
+    Hocon,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OverrideStyle {
+    /// Kafka broker style of the wurstmeister/kafka image, i.e. converts '.' into '_' and
+    /// capitalises all text, never turning the variables of '--exclude' into properties
+    Kafka,
+    /// Lightbend Config style of 'CONFIG_FORCE_' variables, i.e. converts '.' into '_', '-' into
+    /// '__' and '_' into '___', ignoring variables with longer runs of '_'
+    Hocon,
+    /// MicroProfile Config style of Quarkus, i.e. looks up the exact key, then the key with
+    /// non-alphanumeric characters converted into '_', then its capitalised form
+    #[value(name = "microprofile", alias = "quarkus")]
+    MicroProfile,
+    /// .NET style of ASP.NET Core, i.e. converts the hierarchy delimiter into '__', ignoring case
+    #[value(name = "dotnet")]
+    DotNet,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum ManifestKind {
+    /// ConfigMap, holding the output as text, or as binary data when it is not valid UTF-8
+    #[value(name = "config-map", alias = "configmap")]
+    ConfigMap,
+    /// Secret, holding the output encoded in base64
+    Secret,
+}
+
+/// The Kubernetes manifest wrapping the output.
+#[derive(Debug, PartialEq)]
+pub struct Manifest {
+    pub kind: ManifestKind,
+    pub name: String,
+    pub namespace: Option<String>,
+    /// `None` when the key must be derived from the input file and the output format
+    pub data_key: Option<String>,
+}
+
 #[derive(Parser, Debug)]
 /// Generate a properties file from existing properties overriding the values from environment variables and removing all overrides
 pub struct Args {
     #[arg(long)]
     /// If provided, output the properties file to a file instead of standard output
     pub output_file: Option<String>,
+    #[arg(long, short, required_unless_present = "to")]
+    /// Specifies the prefix for environment variables to use for overrides and generation. Only
+    /// optional when converting with '--to', in which case no override is applied
+    pub prefix: Option<String>,
     #[arg(long, short)]
-    /// Specifies the prefix for environment variables to use for overrides and generation
-    pub prefix: String,
-    #[arg(long, short)]
-    /// When selected, uses spring style properties replacement, i.e. converts '.,-' into '_' and
-    /// capitalises all text. Incompatible with -r or --replacement options.
+    /// When selected, uses spring style properties replacement, i.e. converts '.,-' into '_',
+    /// list indices such as '[0]' into '_0' and capitalises all text. Incompatible with -r or
+    /// --replacement options.
     ///
     /// For example, passing '--prefix PREFIX_ --spring' causes environment variable PREFIX_FOO=bar'
-    /// to be interpreted as 'foo=bar'
+    /// to be interpreted as 'foo=bar', and PREFIX_SERVERS_1_HOST=bar as 'servers[1].host=bar'
     pub spring: bool,
     #[arg(long, short)]
     /// Specifies character replacement for case-insensitive interpretation in the format 'c#str'.
@@ -211,45 +547,200 @@ pub struct Args {
     /// For instance, passing '--prefix PREFIX -r .#_ -r _#__' causes environment variable PREFIX_foo_bar__baz=foobar
     /// to be interpreted as 'foo.bar_baz=foobar'. To replace character '-' use '\-' (e.g. '\-#__')
     pub replacement: Vec<String>,
+    #[arg(long, value_enum)]
+    /// Uses a built-in style of properties replacement. Incompatible with --spring, -r or
+    /// --replacement options
+    pub style: Option<OverrideStyle>,
+    #[arg(long)]
+    /// Specifies an environment variable which is never turned into a property with '--style
+    /// kafka', in addition to KAFKA_VERSION, KAFKA_HOME, KAFKA_DEBUG, KAFKA_GC_LOG_OPTS,
+    /// KAFKA_HEAP_OPTS, KAFKA_JMX_OPTS, KAFKA_JVM_PERFORMANCE_OPTS, KAFKA_LOG and KAFKA_OPTS
+    pub exclude: Vec<String>,
+    #[arg(long)]
+    /// Specifies the delimiter of the segments of hierarchical keys with '--style dotnet', such as
+    /// '.' for the keys of JSON files. Defaults to ':', as in 'Logging:LogLevel:Default'
+    pub hierarchy_delimiter: Option<String>,
     /// Original property file to read for override. If not provided, stdin is read instead
     pub file: Option<String>,
     #[arg(long)]
     /// If passed, no input file nor stdin is read for override and only properties generated from
     /// the environment are added to the output
     pub empty_input: bool,
+    #[arg(long, value_enum, default_value = "keep-all")]
+    /// Specifies how keys defined more than once in the input are handled
+    pub duplicates: DuplicatePolicy,
+    #[arg(long, value_enum, default_value = "utf-8")]
+    /// Specifies the character encoding of the input. A UTF-8 byte order mark is always detected
+    /// and stripped
+    pub input_encoding: Encoding,
+    #[arg(long, value_enum)]
+    /// Specifies the character encoding of the output. Defaults to the input encoding. Characters
+    /// which cannot be represented in ISO-8859-1 are written as '\uxxxx' escapes in properties and
+    /// JSON outputs and as character references in XML outputs, and are rejected in the other
+    /// formats
+    pub output_encoding: Option<Encoding>,
+    #[arg(long, value_enum)]
+    /// Specifies the line ending of the output. If not provided, the line ending of the first line
+    /// of the input is used
+    pub line_ending: Option<LineEnding>,
+    #[arg(long)]
+    /// If passed, commented out properties such as '#key=value' are replaced in place with the
+    /// active property when an override exists for their key and the key is not otherwise defined
+    pub activate_commented: bool,
+    #[arg(long, value_enum, visible_alias = "from")]
+    /// Specifies the format of the input, and of the output unless '--to' is passed. If not
+    /// provided, the format is detected from the extension of the input file, or from the content
+    /// of the input when the extension is not recognised
+    pub format: Option<Format>,
+    #[arg(long, value_enum)]
+    /// Converts the input to the given format, writing a new document made of its properties
+    /// after applying the overrides. Fails if a property cannot be represented in the format. An
+    /// input which is already in the format, whether given or detected, is written with its
+    /// formatting retained. Defaults to the format of the extension of '--output-file', if
+    /// recognised
+    pub to: Option<Format>,
+    #[arg(long, value_enum, requires = "name")]
+    /// Wraps the output into a Kubernetes manifest of the given kind, holding the output under the
+    /// key of '--data-key'
+    pub kubernetes: Option<ManifestKind>,
+    #[arg(long, requires = "kubernetes")]
+    /// Specifies the name of the Kubernetes manifest
+    pub name: Option<String>,
+    #[arg(long, requires = "kubernetes")]
+    /// Specifies the namespace of the Kubernetes manifest. If not provided, the manifest has no
+    /// namespace
+    pub namespace: Option<String>,
+    #[arg(long, requires = "kubernetes")]
+    /// Specifies the key holding the output in the data of the Kubernetes manifest. Defaults to
+    /// the name of the input file when the output has its format, or to 'application' followed by
+    /// the extension of the output format
+    pub data_key: Option<String>,
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Configuration {
     pub output_file: Option<String>,
     pub spring: bool,
-    pub prefix: String,
+    pub prefix: Option<String>,
     pub replacement_map: HashMap<char, String>,
+    pub style: Option<OverrideStyle>,
+    pub exclusions: Vec<String>,
+    pub hierarchy_delimiter: String,
     pub file: Option<String>,
     pub empty_input: bool,
+    pub duplicates: DuplicatePolicy,
+    pub input_encoding: Encoding,
+    pub output_encoding: Encoding,
+    pub line_ending: Option<LineEnding>,
+    pub activate_commented: bool,
+    /// `None` when the format must be detected from the content of the input
+    pub format: Option<Format>,
+    /// `None` when the output has the format of the input
+    pub output_format: Option<Format>,
+    pub manifest: Option<Manifest>,
+}
+
+/// Whether `name` is a valid name of Kubernetes objects, a DNS subdomain as defined in RFC 1123.
+fn is_dns_subdomain(name: &str) -> bool {
+    name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+                && label.ends_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
 }
 
 impl Args {
     pub fn validate_and_convert(self) -> Result<Configuration, InternalError> {
         let mut errors: Vec<String> = Vec::new();
+        let format = self
+            .format
+            .or_else(|| self.file.as_deref().and_then(detect_format_of_path));
+        // the output file of a manifest is the manifest itself, not a file of the output format
+        let output_format = self.to.or_else(|| {
+            self.output_file
+                .as_deref()
+                .filter(|_| self.kubernetes.is_none())
+                .and_then(detect_format_of_path)
+        });
+        let manifest = self.kubernetes.map(|kind| Manifest {
+            kind,
+            name: self.name.clone().unwrap_or_default(),
+            namespace: self.namespace.clone(),
+            data_key: self.data_key.clone(),
+        });
         if self.spring && !self.replacement.is_empty() {
             errors.push("replacements are not allowed when 'spring' flag is passed".to_string());
         }
-        if self.prefix == "" {
+        if self.style.is_some() && self.spring {
+            errors.push("'style' is not allowed when 'spring' flag is passed".to_string());
+        }
+        if self.style.is_some() && !self.replacement.is_empty() {
+            errors.push("replacements are not allowed when 'style' is passed".to_string());
+        }
+        if !self.exclude.is_empty() && self.style != Some(OverrideStyle::Kafka) {
+            errors.push("exclusions are only allowed with the 'kafka' style".to_string());
+        }
+        if self.hierarchy_delimiter.is_some() && self.style != Some(OverrideStyle::DotNet) {
+            errors.push("hierarchy delimiter is only allowed with the 'dotnet' style".to_string());
+        }
+        if self
+            .hierarchy_delimiter
+            .as_ref()
+            .is_some_and(|delimiter| delimiter.is_empty())
+        {
+            errors.push("hierarchy delimiter must not be empty".to_string());
+        }
+        if self.prefix.as_ref().is_some_and(|prefix| prefix.is_empty()) {
             errors.push("prefix must not be empty".to_string());
         }
         if self.empty_input && self.file.is_some() {
             errors.push("file cannot be passed if empty_input is present".to_string());
         }
+        for (option, value) in [("name", &self.name), ("namespace", &self.namespace)] {
+            if value
+                .as_deref()
+                .is_some_and(|value| !is_dns_subdomain(value))
+            {
+                errors.push(format!(
+                    "{} must consist of lower case alphanumeric characters, '-' or '.', and start and end with an alphanumeric character",
+                    option
+                ));
+            }
+        }
+        if self.data_key.as_deref().is_some_and(|key| {
+            key.is_empty()
+                || !key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        }) {
+            errors.push(
+                "data key must consist of alphanumeric characters, '-', '_' or '.'".to_string(),
+            );
+        }
         // !self.spring || self.replacement.is_empty()
-        if self.spring && errors.is_empty() {
+        if (self.spring || self.style.is_some()) && errors.is_empty() {
             return Ok(Configuration {
                 output_file: self.output_file,
                 spring: self.spring,
                 replacement_map: HashMap::new(),
+                style: self.style,
+                exclusions: self.exclude,
+                hierarchy_delimiter: self.hierarchy_delimiter.unwrap_or(":".to_string()),
                 prefix: self.prefix,
                 file: self.file,
                 empty_input: self.empty_input,
+                duplicates: self.duplicates,
+                input_encoding: self.input_encoding,
+                output_encoding: self.output_encoding.unwrap_or(self.input_encoding),
+                line_ending: self.line_ending,
+                activate_commented: self.activate_commented,
+                format,
+                output_format,
+                manifest,
             });
         }
 
@@ -287,9 +778,20 @@ impl Args {
             output_file: self.output_file,
             spring: self.spring,
             replacement_map,
+            style: self.style,
+            exclusions: self.exclude,
+            hierarchy_delimiter: self.hierarchy_delimiter.unwrap_or(":".to_string()),
             prefix: self.prefix,
             file: self.file,
             empty_input: self.empty_input,
+            duplicates: self.duplicates,
+            input_encoding: self.input_encoding,
+            output_encoding: self.output_encoding.unwrap_or(self.input_encoding),
+            line_ending: self.line_ending,
+            activate_commented: self.activate_commented,
+            format,
+            output_format,
+            manifest,
         })
     }
 }
@@ -320,10 +822,24 @@ mod args_tests {
             let args = Args {
                 output_file: None,
                 spring: true,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec![".#_".to_string()],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: None,
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_argument_validation_error(
@@ -337,10 +853,24 @@ mod args_tests {
             let args = Args {
                 output_file: None,
                 spring: true,
-                prefix: "".to_string(),
+                prefix: Some("".to_string()),
                 replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: None,
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_argument_validation_error(
@@ -354,10 +884,24 @@ mod args_tests {
             let args = Args {
                 output_file: None,
                 spring: true,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: Some("file".to_string()),
                 empty_input: true,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_argument_validation_error(
@@ -371,10 +915,24 @@ mod args_tests {
             let args = Args {
                 output_file: Some("output2".to_string()),
                 spring: true,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: Some("file1".to_string()),
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_eq!(
@@ -382,10 +940,21 @@ mod args_tests {
                 Configuration {
                     output_file: Some("output2".to_string()),
                     spring: true,
-                    prefix: "PREFIX_".to_string(),
+                    prefix: Some("PREFIX_".to_string()),
                     replacement_map: HashMap::new(),
+                    style: None,
+                    exclusions: vec![],
+                    hierarchy_delimiter: ":".to_string(),
                     file: Some("file1".to_string()),
-                    empty_input: false
+                    empty_input: false,
+                    duplicates: DuplicatePolicy::KeepAll,
+                    input_encoding: Encoding::Utf8,
+                    output_encoding: Encoding::Utf8,
+                    line_ending: None,
+                    activate_commented: false,
+                    format: None,
+                    output_format: None,
+                    manifest: None,
                 }
             )
         }
@@ -395,10 +964,24 @@ mod args_tests {
             let args = Args {
                 output_file: None,
                 spring: false,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec!["invalid".to_string()],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: None,
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_argument_validation_error(&args.validate_and_convert(),
@@ -411,10 +994,24 @@ mod args_tests {
             let args = Args {
                 output_file: None,
                 spring: false,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec!["asdf#str".to_string()],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: None,
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_argument_validation_error(
@@ -428,10 +1025,24 @@ mod args_tests {
             let args = Args {
                 output_file: None,
                 spring: false,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec!["invalid1".to_string(), "fdas#str".to_string()],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: None,
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             let result = args.validate_and_convert();
@@ -453,10 +1064,24 @@ mod args_tests {
             let args = Args {
                 output_file: None,
                 spring: false,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec!["-#__".to_string(), ".#_".to_string()],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: None,
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_eq!(
@@ -464,13 +1089,24 @@ mod args_tests {
                 Configuration {
                     output_file: None,
                     spring: false,
-                    prefix: "PREFIX_".to_string(),
+                    prefix: Some("PREFIX_".to_string()),
                     replacement_map: hashmap! {
                         '.' => "_".to_string(),
                         '-' => "__".to_string(),
                     },
+                    style: None,
+                    exclusions: vec![],
+                    hierarchy_delimiter: ":".to_string(),
                     file: None,
                     empty_input: false,
+                    duplicates: DuplicatePolicy::KeepAll,
+                    input_encoding: Encoding::Utf8,
+                    output_encoding: Encoding::Utf8,
+                    line_ending: None,
+                    activate_commented: false,
+                    format: None,
+                    output_format: None,
+                    manifest: None,
                 }
             )
         }
@@ -480,10 +1116,24 @@ mod args_tests {
             let args = Args {
                 output_file: None,
                 spring: false,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec!["\\-#__".to_string(), ".#_".to_string()],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: None,
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_eq!(
@@ -491,26 +1141,144 @@ mod args_tests {
                 Configuration {
                     output_file: None,
                     spring: false,
-                    prefix: "PREFIX_".to_string(),
+                    prefix: Some("PREFIX_".to_string()),
                     replacement_map: hashmap! {
                         '.' => "_".to_string(),
                         '-' => "__".to_string(),
                     },
+                    style: None,
+                    exclusions: vec![],
+                    hierarchy_delimiter: ":".to_string(),
                     file: None,
                     empty_input: false,
+                    duplicates: DuplicatePolicy::KeepAll,
+                    input_encoding: Encoding::Utf8,
+                    output_encoding: Encoding::Utf8,
+                    line_ending: None,
+                    activate_commented: false,
+                    format: None,
+                    output_format: None,
+                    manifest: None,
                 }
             )
         }
 
+        #[test]
+        fn should_return_configuration_with_duplicate_policy() {
+            let args = Args {
+                output_file: None,
+                spring: true,
+                prefix: Some("PREFIX_".to_string()),
+                replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::LastWins,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            assert_eq!(
+                args.validate_and_convert().unwrap().duplicates,
+                DuplicatePolicy::LastWins
+            )
+        }
+
+        #[test]
+        fn should_default_output_encoding_to_input_encoding() {
+            let args = Args {
+                output_file: None,
+                spring: true,
+                prefix: Some("PREFIX_".to_string()),
+                replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Iso88591,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            assert_eq!(
+                args.validate_and_convert().unwrap().output_encoding,
+                Encoding::Iso88591
+            )
+        }
+
+        #[test]
+        fn should_return_configuration_with_explicit_output_encoding() {
+            let args = Args {
+                output_file: None,
+                spring: true,
+                prefix: Some("PREFIX_".to_string()),
+                replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Iso88591,
+                output_encoding: Some(Encoding::Utf8),
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            assert_eq!(
+                args.validate_and_convert().unwrap().output_encoding,
+                Encoding::Utf8
+            )
+        }
+
         #[test]
         fn should_trim_spaces_in_replacement_mapping() {
             let args = Args {
                 output_file: Some("foo".to_string()),
                 spring: false,
-                prefix: "PREFIX_".to_string(),
+                prefix: Some("PREFIX_".to_string()),
                 replacement: vec![" - # __ ".to_string(), "  .  # _ ".to_string()],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
                 file: None,
                 empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
             };
 
             assert_eq!(
@@ -518,15 +1286,297 @@ mod args_tests {
                 Configuration {
                     output_file: Some("foo".to_string()),
                     spring: false,
-                    prefix: "PREFIX_".to_string(),
+                    prefix: Some("PREFIX_".to_string()),
                     replacement_map: hashmap! {
                         '.' => "_".to_string(),
                         '-' => "__".to_string(),
                     },
+                    style: None,
+                    exclusions: vec![],
+                    hierarchy_delimiter: ":".to_string(),
                     file: None,
                     empty_input: false,
+                    duplicates: DuplicatePolicy::KeepAll,
+                    input_encoding: Encoding::Utf8,
+                    output_encoding: Encoding::Utf8,
+                    line_ending: None,
+                    activate_commented: false,
+                    format: None,
+                    output_format: None,
+                    manifest: None,
                 }
             )
         }
+
+        #[test]
+        fn should_allow_conversion_without_prefix() {
+            let args = Args {
+                output_file: None,
+                spring: false,
+                prefix: None,
+                replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: Some(Format::Properties),
+                to: Some(Format::Dotenv),
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            let configuration = args.validate_and_convert().unwrap();
+
+            assert_eq!(configuration.prefix, None);
+            assert_eq!(configuration.format, Some(Format::Properties));
+            assert_eq!(configuration.output_format, Some(Format::Dotenv));
+        }
+
+        #[test]
+        fn should_detect_formats_from_file_extensions() {
+            let args = Args {
+                output_file: Some(".env".to_string()),
+                spring: false,
+                prefix: Some("PREFIX".to_string()),
+                replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
+                file: Some("config/application.yml".to_string()),
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            let configuration = args.validate_and_convert().unwrap();
+
+            assert_eq!(configuration.format, Some(Format::Yaml));
+            assert_eq!(configuration.output_format, Some(Format::Dotenv));
+        }
+
+        #[test]
+        fn should_be_invalid_if_manifest_names_are_invalid() {
+            let args = Args {
+                output_file: None,
+                spring: false,
+                prefix: Some("PREFIX".to_string()),
+                replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: Some(ManifestKind::ConfigMap),
+                name: Some("App-".to_string()),
+                namespace: Some("prod".to_string()),
+                data_key: Some("app/config".to_string()),
+            };
+
+            assert_argument_validation_error(
+                &args.validate_and_convert(),
+                &vec![
+                    "name must consist of lower case alphanumeric characters, '-' or '.', and start and end with an alphanumeric character".to_string(),
+                    "data key must consist of alphanumeric characters, '-', '_' or '.'".to_string(),
+                ],
+            );
+        }
+
+        #[test]
+        fn should_not_detect_output_format_from_manifest_file() {
+            let args = Args {
+                output_file: Some("configmap.yaml".to_string()),
+                spring: false,
+                prefix: Some("PREFIX".to_string()),
+                replacement: vec![],
+                style: None,
+                exclude: vec![],
+                hierarchy_delimiter: None,
+                file: Some("application.properties".to_string()),
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: Some(ManifestKind::Secret),
+                name: Some("app.config-1".to_string()),
+                namespace: None,
+                data_key: None,
+            };
+
+            let configuration = args.validate_and_convert().unwrap();
+
+            assert_eq!(configuration.format, Some(Format::Properties));
+            assert_eq!(configuration.output_format, None);
+            assert_eq!(
+                configuration.manifest,
+                Some(Manifest {
+                    kind: ManifestKind::Secret,
+                    name: "app.config-1".to_string(),
+                    namespace: None,
+                    data_key: None,
+                })
+            );
+        }
+
+        #[test]
+        fn should_be_invalid_if_style_combined_with_other_replacements() {
+            let args = Args {
+                output_file: None,
+                spring: true,
+                prefix: Some("KAFKA_".to_string()),
+                replacement: vec![".#_".to_string()],
+                style: Some(OverrideStyle::Kafka),
+                exclude: vec![],
+                hierarchy_delimiter: None,
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            assert_argument_validation_error(
+                &args.validate_and_convert(),
+                &vec![
+                    "replacements are not allowed when 'spring' flag is passed".to_string(),
+                    "'style' is not allowed when 'spring' flag is passed".to_string(),
+                    "replacements are not allowed when 'style' is passed".to_string(),
+                ],
+            );
+        }
+
+        #[test]
+        fn should_be_invalid_if_exclusions_passed_without_kafka_style() {
+            let args = Args {
+                output_file: None,
+                spring: true,
+                prefix: Some("KAFKA_".to_string()),
+                replacement: vec![],
+                style: None,
+                exclude: vec!["KAFKA_CREATE_TOPICS".to_string()],
+                hierarchy_delimiter: None,
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            assert_argument_validation_error(
+                &args.validate_and_convert(),
+                &vec!["exclusions are only allowed with the 'kafka' style".to_string()],
+            );
+        }
+
+        #[test]
+        fn should_return_configuration_with_style_and_exclusions() {
+            let args = Args {
+                output_file: None,
+                spring: false,
+                prefix: Some("KAFKA_".to_string()),
+                replacement: vec![],
+                style: Some(OverrideStyle::Kafka),
+                exclude: vec!["KAFKA_CREATE_TOPICS".to_string()],
+                hierarchy_delimiter: None,
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            let configuration = args.validate_and_convert().unwrap();
+
+            assert_eq!(configuration.style, Some(OverrideStyle::Kafka));
+            assert_eq!(
+                configuration.exclusions,
+                vec!["KAFKA_CREATE_TOPICS".to_string()]
+            );
+        }
+
+        #[test]
+        fn should_be_invalid_if_hierarchy_delimiter_passed_without_dotnet_style() {
+            let args = Args {
+                output_file: None,
+                spring: false,
+                prefix: Some("MyApp_".to_string()),
+                replacement: vec![],
+                style: Some(OverrideStyle::Kafka),
+                exclude: vec![],
+                hierarchy_delimiter: Some("".to_string()),
+                file: None,
+                empty_input: false,
+                duplicates: DuplicatePolicy::KeepAll,
+                input_encoding: Encoding::Utf8,
+                output_encoding: None,
+                line_ending: None,
+                activate_commented: false,
+                format: None,
+                to: None,
+                kubernetes: None,
+                name: None,
+                namespace: None,
+                data_key: None,
+            };
+
+            assert_argument_validation_error(
+                &args.validate_and_convert(),
+                &vec![
+                    "hierarchy delimiter is only allowed with the 'dotnet' style".to_string(),
+                    "hierarchy delimiter must not be empty".to_string(),
+                ],
+            );
+        }
     }
 }
@@ -1,10 +1,17 @@
+use crate::keys::{key_segments, Segment};
 use crate::model::Property;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub trait Overrider {
     fn resolve_substitution(&self, key: &str, prefix: Option<&str>) -> Option<&str>;
-    fn generate_additions(&self, prefix: &str) -> Vec<Property>;
+    fn generate_additions(&self, prefix: &str) -> Vec<Property<'static>>;
+
+    /// The form of `key` under which keys overridden by the same variables are equal, used to
+    /// avoid adding properties which are already defined.
+    fn canonical_key(&self, key: &str) -> String {
+        key.to_string()
+    }
 }
 
 #[derive(Clone)]
@@ -91,12 +98,14 @@ impl Overrider for SpringStyleOverrider {
             + key
                 .replace(".", "_")
                 .replace("-", "_")
+                .replace("[", "_")
+                .replace("]", "")
                 .to_uppercase()
                 .as_str();
         self.env.get(variable_to_resolve)
     }
 
-    fn generate_additions(&self, prefix: &str) -> Vec<Property> {
+    fn generate_additions(&self, prefix: &str) -> Vec<Property<'static>> {
         let prefix_match = prefix.to_string();
         let prefixed_entries: HashMap<&str, &str> = self
             .env
@@ -108,10 +117,23 @@ impl Overrider for SpringStyleOverrider {
         prefixed_entries
             .into_iter()
             .map(|(key, value)| {
-                let new_key = key
+                let mut new_key = String::new();
+                for (i, part) in key
                     .trim_start_matches(&prefix_match)
-                    .replace("_", ".")
-                    .to_lowercase();
+                    .to_lowercase()
+                    .split('_')
+                    .enumerate()
+                {
+                    // numeric parts are list indices, as in 'SERVERS_1_HOST' for 'servers[1].host'
+                    if i > 0 && !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()) {
+                        new_key.push_str(format!("[{}]", part).as_str());
+                    } else {
+                        if i > 0 {
+                            new_key.push('.');
+                        }
+                        new_key.push_str(part);
+                    }
+                }
                 Property::new(new_key.as_str(), value)
             })
             .collect()
@@ -262,6 +284,23 @@ mod spring_style_overrider_tests {
                 Some("value4")
             );
         }
+
+        #[test]
+        fn should_replace_list_indices_with_underscore_and_index_for_lookup() {
+            let testee = make(hashmap! {
+                "MY_LIST_0_NAME" => "value1",
+                "MATRIX_1_2" => "value2"
+            });
+
+            assert_eq!(
+                testee.resolve_substitution("my.list[0].name", None),
+                Some("value1")
+            );
+            assert_eq!(
+                testee.resolve_substitution("matrix[1][2]", None),
+                Some("value2")
+            );
+        }
     }
 
     #[cfg(test)]
@@ -314,6 +353,716 @@ mod spring_style_overrider_tests {
                 ],
             );
         }
+
+        #[test]
+        fn should_convert_numeric_parts_into_list_indices() {
+            let testee = make(hashmap! {
+                "PREFIX_SERVERS_1_HOST" => "value1",
+                "PREFIX_MATRIX_0_12" => "value2",
+                "PREFIX_0_FOO" => "value3",
+                "PREFIX_HOST_V2" => "value4"
+            });
+
+            assert_contains_exactly_in_any_order(
+                testee.generate_additions(PREFIX),
+                vec![
+                    Property::new("servers[1].host", "value1"),
+                    Property::new("matrix[0][12]", "value2"),
+                    Property::new("0.foo", "value3"),
+                    Property::new("host.v2", "value4"),
+                ],
+            );
+        }
+    }
+}
+
+/// Overrides properties as the `start-kafka.sh` script of the wurstmeister/kafka image does: the
+/// variable of a key is its upper case with '.' replaced by '_', and added properties are the
+/// lower case of the variables with '_' replaced by '.'. Excluded variables, such as the
+/// KAFKA_HEAP_OPTS used to configure the JVM, are never turned into properties.
+pub struct KafkaStyleOverrider {
+    env: Environment,
+    exclusions: HashSet<String>,
+}
+
+impl KafkaStyleOverrider {
+    /// The variables excluded by `start-kafka.sh`, which configure the container rather than the
+    /// broker.
+    pub const DEFAULT_EXCLUSIONS: [&'static str; 9] = [
+        "KAFKA_VERSION",
+        "KAFKA_HOME",
+        "KAFKA_DEBUG",
+        "KAFKA_GC_LOG_OPTS",
+        "KAFKA_HEAP_OPTS",
+        "KAFKA_JMX_OPTS",
+        "KAFKA_JVM_PERFORMANCE_OPTS",
+        "KAFKA_LOG",
+        "KAFKA_OPTS",
+    ];
+
+    /// Creates an overrider excluding `exclusions` in addition to `DEFAULT_EXCLUSIONS`.
+    pub fn new<S: AsRef<str>>(env: Environment, exclusions: &[S]) -> KafkaStyleOverrider {
+        KafkaStyleOverrider {
+            env,
+            exclusions: Self::DEFAULT_EXCLUSIONS
+                .iter()
+                .map(|exclusion| exclusion.to_string())
+                .chain(
+                    exclusions
+                        .iter()
+                        .map(|exclusion| exclusion.as_ref().to_string()),
+                )
+                .collect(),
+        }
+    }
+}
+
+impl Overrider for KafkaStyleOverrider {
+    fn resolve_substitution(&self, key: &str, prefix: Option<&str>) -> Option<&str> {
+        let variable_to_resolve =
+            prefix.unwrap_or("").to_string() + key.replace(".", "_").to_uppercase().as_str();
+        if self.exclusions.contains(&variable_to_resolve) {
+            return None;
+        }
+        self.env.get(variable_to_resolve)
+    }
+
+    fn generate_additions(&self, prefix: &str) -> Vec<Property<'static>> {
+        self.env
+            .env
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix) && !self.exclusions.contains(*key))
+            .map(|(key, value)| {
+                let new_key = key[prefix.len()..].replace("_", ".").to_lowercase();
+                Property::new(new_key.as_str(), value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod kafka_style_overrider_tests {
+    use super::*;
+
+    fn make(map: HashMap<&str, &str>) -> KafkaStyleOverrider {
+        KafkaStyleOverrider::new(Environment::new(&map), &[] as &[&str])
+    }
+
+    #[cfg(test)]
+    mod resolve_tests {
+        use super::*;
+
+        #[test]
+        fn should_replace_dots_with_underscores_and_capitalise() {
+            let testee = make(hashmap! {
+                "KAFKA_LOG_RETENTION_HOURS" => "24"
+            });
+
+            assert_eq!(
+                testee.resolve_substitution("log.retention.hours", Some("KAFKA_")),
+                Some("24")
+            );
+        }
+
+        #[test]
+        fn should_not_resolve_excluded_variables() {
+            let testee = make(hashmap! {
+                "KAFKA_HEAP_OPTS" => "-Xmx1G"
+            });
+
+            assert_eq!(
+                testee.resolve_substitution("heap.opts", Some("KAFKA_")),
+                None
+            );
+        }
+    }
+
+    #[cfg(test)]
+    mod addition_tests {
+        use super::*;
+        use crate::test_utils::assert_contains_exactly_in_any_order;
+
+        #[test]
+        fn should_convert_prefixed_variables_into_lower_case_dotted_keys() {
+            let testee = make(hashmap! {
+                "KAFKA_BROKER_ID" => "1",
+                "KAFKA_ADVERTISED_LISTENERS" => "PLAINTEXT://kafka:9092",
+                "ZOOKEEPER_CONNECT" => "zookeeper:2181"
+            });
+
+            assert_contains_exactly_in_any_order(
+                testee.generate_additions("KAFKA_"),
+                vec![
+                    Property::new("broker.id", "1"),
+                    Property::new("advertised.listeners", "PLAINTEXT://kafka:9092"),
+                ],
+            );
+        }
+
+        #[test]
+        fn should_skip_default_and_configured_exclusions() {
+            let testee = KafkaStyleOverrider::new(
+                Environment::new(&hashmap! {
+                    "KAFKA_VERSION" => "2.8.1",
+                    "KAFKA_HOME" => "/opt/kafka",
+                    "KAFKA_JMX_OPTS" => "-Dcom.sun.management.jmxremote",
+                    "KAFKA_CREATE_TOPICS" => "topic:1:1",
+                    "KAFKA_NUM_PARTITIONS" => "3"
+                }),
+                &["KAFKA_CREATE_TOPICS"],
+            );
+
+            assert_contains_exactly_in_any_order(
+                testee.generate_additions("KAFKA_"),
+                vec![Property::new("num.partitions", "3")],
+            );
+        }
+    }
+}
+
+/// Overrides properties as Lightbend Config does with the 'CONFIG_FORCE_' variables read when
+/// `config.override_with_env_vars` is set: in the variable of a key, '.' is replaced by '_', '-'
+/// by '__' and '_' by '___', case-sensitively. As in `ConfigImpl.envVariableAsProperty`, runs of
+/// more than three underscores have no mapping, so their variables are never turned into
+/// properties. Elements of arrays are addressed by number, so that 'list[0]' is overridden by
+/// the variable of 'list.0'.
+pub struct HoconStyleOverrider {
+    env: Environment,
+}
+
+impl HoconStyleOverrider {
+    pub fn new(env: Environment) -> HoconStyleOverrider {
+        HoconStyleOverrider { env }
+    }
+
+    /// The path expression of `key`, addressing the elements of arrays by number.
+    fn path(key: &str) -> String {
+        key_segments(key)
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(name) => name.clone(),
+                Segment::Index(index) => index.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    fn encode(key: &str) -> String {
+        let mut variable = String::with_capacity(key.len());
+        for c in key.chars() {
+            match c {
+                '.' => variable.push('_'),
+                '-' => variable.push_str("__"),
+                '_' => variable.push_str("___"),
+                c => variable.push(c),
+            }
+        }
+        variable
+    }
+
+    /// Decodes the variable name following the prefix into its key, or `None` when it contains a
+    /// run of more than three underscores.
+    fn decode(name: &str) -> Option<String> {
+        fn separator(underscores: usize) -> Option<&'static str> {
+            match underscores {
+                0 => Some(""),
+                1 => Some("."),
+                2 => Some("-"),
+                3 => Some("_"),
+                _ => None,
+            }
+        }
+        let mut key = String::with_capacity(name.len());
+        let mut underscores: usize = 0;
+        for c in name.chars() {
+            if c == '_' {
+                underscores += 1;
+                continue;
+            }
+            key.push_str(separator(underscores)?);
+            key.push(c);
+            underscores = 0;
+        }
+        key.push_str(separator(underscores)?);
+        Some(key)
+    }
+}
+
+impl Overrider for HoconStyleOverrider {
+    fn resolve_substitution(&self, key: &str, prefix: Option<&str>) -> Option<&str> {
+        let path = Self::path(key);
+        let name = Self::encode(&path);
+        // a key such as 'a_.b' is encoded as a run of underscores decoded into another key
+        if Self::decode(&name) != Some(path) {
+            return None;
+        }
+        self.env
+            .get(prefix.unwrap_or("").to_string() + name.as_str())
+    }
+
+    fn generate_additions(&self, prefix: &str) -> Vec<Property<'static>> {
+        self.env
+            .env
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .filter_map(|(key, value)| {
+                let new_key = Self::decode(&key[prefix.len()..])?;
+                Some(Property::new(new_key.as_str(), value))
+            })
+            .collect()
+    }
+
+    /// Elements of arrays are equal to the numeric keys generated from their variables.
+    fn canonical_key(&self, key: &str) -> String {
+        Self::path(key)
+    }
+}
+
+#[cfg(test)]
+mod hocon_style_overrider_tests {
+    use super::*;
+
+    const PREFIX: &str = "CONFIG_FORCE_";
+
+    fn make(map: HashMap<&str, &str>) -> HoconStyleOverrider {
+        HoconStyleOverrider::new(Environment::new(&map))
+    }
+
+    #[cfg(test)]
+    mod resolve_tests {
+        use super::*;
+
+        #[test]
+        fn should_encode_separators_case_sensitively() {
+            let testee = make(hashmap! {
+                "CONFIG_FORCE_akka_remote_artery__canonical_bind___port" => "2552",
+                "CONFIG_FORCE_A_B" => "upper"
+            });
+
+            assert_eq!(
+                testee.resolve_substitution("akka.remote.artery-canonical.bind_port", Some(PREFIX)),
+                Some("2552")
+            );
+            assert_eq!(testee.resolve_substitution("a.b", Some(PREFIX)), None);
+        }
+
+        #[test]
+        fn should_address_array_elements_by_number() {
+            let testee = make(hashmap! {
+                "CONFIG_FORCE_akka_list_0_name" => "a"
+            });
+
+            assert_eq!(
+                testee.resolve_substitution("akka.list[0].name", Some(PREFIX)),
+                Some("a")
+            );
+            assert_eq!(
+                testee.canonical_key("akka.list[0].name"),
+                "akka.list.0.name"
+            );
+        }
+
+        #[test]
+        fn should_not_resolve_keys_without_unambiguous_variable() {
+            let testee = make(hashmap! {
+                "CONFIG_FORCE_a____b" => "value"
+            });
+
+            assert_eq!(testee.resolve_substitution("a_.b", Some(PREFIX)), None);
+            assert_eq!(testee.resolve_substitution("a.-b", Some(PREFIX)), None);
+        }
+    }
+
+    #[cfg(test)]
+    mod addition_tests {
+        use super::*;
+        use crate::test_utils::assert_contains_exactly_in_any_order;
+
+        #[test]
+        fn should_decode_underscore_runs_as_lightbend_config() {
+            let testee = make(hashmap! {
+                "CONFIG_FORCE_a_b_c" => "1",
+                "CONFIG_FORCE_a__c" => "2",
+                "CONFIG_FORCE_a___c" => "3",
+                "CONFIG_FORCE_a_b__c___d" => "4",
+                "CONFIG_FORCE__a_" => "5",
+                "CONFIG_FORCE_FOO_BAR" => "6"
+            });
+
+            assert_contains_exactly_in_any_order(
+                testee.generate_additions(PREFIX),
+                vec![
+                    Property::new("a.b.c", "1"),
+                    Property::new("a-c", "2"),
+                    Property::new("a_c", "3"),
+                    Property::new("a.b-c_d", "4"),
+                    Property::new(".a.", "5"),
+                    Property::new("FOO.BAR", "6"),
+                ],
+            );
+        }
+
+        #[test]
+        fn should_skip_variables_with_unmapped_underscore_runs() {
+            let testee = make(hashmap! {
+                "CONFIG_FORCE_a____b" => "1",
+                "CONFIG_FORCE_a_____b" => "2",
+                "CONFIG_FORCE_c____" => "3",
+                "OTHER_a_b" => "4"
+            });
+
+            assert_contains_exactly_in_any_order(testee.generate_additions(PREFIX), vec![]);
+        }
+    }
+}
+
+/// Overrides properties as MicroProfile Config implementations such as Quarkus do: the value of a
+/// key is looked up in the variable named exactly as the key, then in the variable with every
+/// non-alphanumeric character replaced by '_', and finally in its upper case. Quoted segments such
+/// as '"a.b"' in 'datasource."a.b".url' have their quotes replaced as well, giving
+/// 'DATASOURCE__A_B__URL'. Added properties are the lower case of the variables with '_'
+/// replaced by '.', '__' enclosing a quoted segment and a leading '_' replaced by the '%' of
+/// profiles, while variables with characters other than letters, digits and '_' are added with
+/// their exact name.
+pub struct MicroProfileStyleOverrider {
+    env: Environment,
+}
+
+impl MicroProfileStyleOverrider {
+    pub fn new(env: Environment) -> MicroProfileStyleOverrider {
+        MicroProfileStyleOverrider { env }
+    }
+
+    fn sanitize(key: &str) -> String {
+        key.chars()
+            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+
+    fn to_key(name: &str) -> String {
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return name.to_string();
+        }
+        let mut key = String::with_capacity(name.len() + 2);
+        let mut quoted = false;
+        let mut rest = name.to_lowercase();
+        if let Some(profile) = rest.strip_prefix('_') {
+            key.push('%');
+            rest = profile.to_string();
+        }
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('_', Some('_')) => {
+                    chars.next();
+                    key.push_str(if quoted { "\"." } else { ".\"" });
+                    quoted = !quoted;
+                }
+                ('_', _) => key.push('.'),
+                (c, _) => key.push(c),
+            }
+        }
+        if quoted {
+            key.push('"');
+        }
+        key
+    }
+}
+
+impl Overrider for MicroProfileStyleOverrider {
+    fn resolve_substitution(&self, key: &str, prefix: Option<&str>) -> Option<&str> {
+        let prefix = prefix.unwrap_or("");
+        let sanitized = Self::sanitize(key);
+        [key.to_string(), sanitized.clone(), sanitized.to_uppercase()]
+            .into_iter()
+            .find_map(|name| self.env.get(prefix.to_string() + name.as_str()))
+    }
+
+    fn generate_additions(&self, prefix: &str) -> Vec<Property<'static>> {
+        self.env
+            .env
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, value)| {
+                let new_key = Self::to_key(&key[prefix.len()..]);
+                Property::new(new_key.as_str(), value)
+            })
+            .collect()
+    }
+
+    /// Keys are compared by the upper case variable name they are looked up in, so that the
+    /// property added for the variable of 'max-size' is the existing 'max-size'.
+    fn canonical_key(&self, key: &str) -> String {
+        Self::sanitize(key).to_uppercase()
+    }
+}
+
+#[cfg(test)]
+mod micro_profile_style_overrider_tests {
+    use super::*;
+
+    const PREFIX: &str = "APP_";
+
+    fn make(map: HashMap<&str, &str>) -> MicroProfileStyleOverrider {
+        MicroProfileStyleOverrider::new(Environment::new(&map))
+    }
+
+    #[cfg(test)]
+    mod resolve_tests {
+        use super::*;
+
+        #[test]
+        fn should_look_up_exact_then_sanitized_then_upper_case_name() {
+            let testee = make(hashmap! {
+                "APP_com.acme.size" => "exact",
+                "APP_com_acme_size" => "sanitized",
+                "APP_COM_ACME_SIZE" => "upper",
+                "APP_com_acme_name" => "sanitized name",
+                "APP_COM_ACME_NAME" => "upper name",
+                "APP_COM_ACME_COLOR" => "upper color"
+            });
+
+            assert_eq!(
+                testee.resolve_substitution("com.acme.size", Some(PREFIX)),
+                Some("exact")
+            );
+            assert_eq!(
+                testee.resolve_substitution("com.acme.name", Some(PREFIX)),
+                Some("sanitized name")
+            );
+            assert_eq!(
+                testee.resolve_substitution("com.acme.color", Some(PREFIX)),
+                Some("upper color")
+            );
+        }
+
+        #[test]
+        fn should_replace_quotes_and_profiles() {
+            let testee = make(hashmap! {
+                "APP_QUARKUS_DATASOURCE__USERS_DB__JDBC_URL" => "jdbc:postgresql:users",
+                "APP__DEV_QUARKUS_HTTP_PORT" => "8081"
+            });
+
+            assert_eq!(
+                testee
+                    .resolve_substitution("quarkus.datasource.\"users-db\".jdbc.url", Some(PREFIX)),
+                Some("jdbc:postgresql:users")
+            );
+            assert_eq!(
+                testee.resolve_substitution("%dev.quarkus.http.port", Some(PREFIX)),
+                Some("8081")
+            );
+        }
+    }
+
+    #[test]
+    fn canonical_key_should_equal_additions_of_variables_overriding_key() {
+        let testee = make(hashmap! {
+            "APP_QUARKUS_DATASOURCE_JDBC_MAX_SIZE" => "20",
+            "APP__DEV_QUARKUS_HTTP_PORT" => "8081"
+        });
+        let defined: HashSet<String> =
+            ["quarkus.datasource.jdbc.max-size", "%dev.quarkus.http.port"]
+                .into_iter()
+                .map(|key| testee.canonical_key(key))
+                .collect();
+
+        assert!(testee
+            .generate_additions(PREFIX)
+            .iter()
+            .all(|property| defined.contains(&testee.canonical_key(&property.key))));
+    }
+
+    #[cfg(test)]
+    mod addition_tests {
+        use super::*;
+        use crate::test_utils::assert_contains_exactly_in_any_order;
+
+        #[test]
+        fn should_convert_variables_into_dotted_keys() {
+            let testee = make(hashmap! {
+                "APP_QUARKUS_HTTP_PORT" => "8080",
+                "APP_QUARKUS_DATASOURCE__USERS__JDBC_URL" => "jdbc:h2:mem:users",
+                "APP__DEV_QUARKUS_LOG_LEVEL" => "DEBUG",
+                "APP_QUARKUS_DATASOURCE__REPORTS" => "x",
+                "APP_com.acme.Size" => "10",
+                "OTHER_FOO" => "y"
+            });
+
+            assert_contains_exactly_in_any_order(
+                testee.generate_additions(PREFIX),
+                vec![
+                    Property::new("quarkus.http.port", "8080"),
+                    Property::new("quarkus.datasource.\"users\".jdbc.url", "jdbc:h2:mem:users"),
+                    Property::new("%dev.quarkus.log.level", "DEBUG"),
+                    Property::new("quarkus.datasource.\"reports\"", "x"),
+                    Property::new("com.acme.Size", "10"),
+                ],
+            );
+        }
+    }
+}
+
+/// Overrides properties as the environment variables configuration provider of .NET does: the
+/// segments of hierarchical keys such as 'Logging:LogLevel:Default' are separated by '__' in
+/// variables, as in 'Logging__LogLevel__Default', and variables are matched case-insensitively,
+/// prefix included. The segments of keys are separated by `delimiter`, so that the keys of
+/// flattened 'appsettings.json' files such as 'Logging.LogLevel.Default' can be addressed with
+/// '.'.
+pub struct DotNetStyleOverrider {
+    /// The variables starting with the prefix, ignoring case, with their name following the prefix
+    /// and '__' replaced by ':' as in `EnvironmentVariablesConfigurationProvider.Normalize`, sorted
+    /// by name
+    variables: Vec<(String, String)>,
+    delimiter: String,
+}
+
+impl DotNetStyleOverrider {
+    pub fn new<S: AsRef<str>>(
+        env: Environment,
+        prefix: &str,
+        delimiter: S,
+    ) -> DotNetStyleOverrider {
+        let mut variables: Vec<(String, String)> = env
+            .env
+            .into_iter()
+            .filter(|(key, _)| {
+                key.get(..prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+            })
+            .map(|(key, value)| (key[prefix.len()..].replace("__", ":"), value))
+            .collect();
+        variables.sort();
+        DotNetStyleOverrider {
+            variables,
+            delimiter: delimiter.as_ref().to_string(),
+        }
+    }
+}
+
+impl Overrider for DotNetStyleOverrider {
+    fn resolve_substitution(&self, key: &str, _prefix: Option<&str>) -> Option<&str> {
+        let path = key
+            .split(self.delimiter.as_str())
+            .collect::<Vec<&str>>()
+            .join(":");
+        self.variables
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&path))
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn generate_additions(&self, _prefix: &str) -> Vec<Property<'static>> {
+        self.variables
+            .iter()
+            .map(|(name, value)| {
+                let new_key = name.split(':').collect::<Vec<&str>>().join(&self.delimiter);
+                Property::new(new_key.as_str(), value)
+            })
+            .collect()
+    }
+
+    /// Keys are compared case-insensitively, whatever their delimiter.
+    fn canonical_key(&self, key: &str) -> String {
+        key.split(self.delimiter.as_str())
+            .collect::<Vec<&str>>()
+            .join(":")
+            .to_lowercase()
+    }
+}
+
+#[cfg(test)]
+mod dot_net_style_overrider_tests {
+    use super::*;
+
+    const PREFIX: &str = "MyApp_";
+
+    fn make(map: HashMap<&str, &str>, delimiter: &str) -> DotNetStyleOverrider {
+        DotNetStyleOverrider::new(Environment::new(&map), PREFIX, delimiter)
+    }
+
+    #[cfg(test)]
+    mod resolve_tests {
+        use super::*;
+
+        #[test]
+        fn should_match_double_underscores_case_insensitively() {
+            let testee = make(
+                hashmap! {
+                    "MYAPP_LOGGING__LOGLEVEL__DEFAULT" => "Warning",
+                    "MyApp_ConnectionStrings:Default" => "Server=db"
+                },
+                ":",
+            );
+
+            assert_eq!(
+                testee.resolve_substitution("Logging:LogLevel:Default", Some(PREFIX)),
+                Some("Warning")
+            );
+            assert_eq!(
+                testee.resolve_substitution("connectionstrings:default", Some(PREFIX)),
+                Some("Server=db")
+            );
+            assert_eq!(
+                testee.resolve_substitution("Logging:LogLevel", Some(PREFIX)),
+                None
+            );
+        }
+
+        #[test]
+        fn should_split_keys_with_configured_delimiter() {
+            let testee = make(
+                hashmap! {
+                    "MyApp_Logging__LogLevel__Default" => "Warning"
+                },
+                ".",
+            );
+
+            assert_eq!(
+                testee.resolve_substitution("Logging.LogLevel.Default", Some(PREFIX)),
+                Some("Warning")
+            );
+        }
+    }
+
+    #[test]
+    fn canonical_key_should_ignore_case() {
+        let testee = make(hashmap! {}, ".");
+
+        assert_eq!(
+            testee.canonical_key("Logging.LogLevel"),
+            testee.canonical_key("LOGGING.LOGLEVEL")
+        );
+    }
+
+    #[cfg(test)]
+    mod addition_tests {
+        use super::*;
+        use crate::test_utils::assert_contains_exactly_in_any_order;
+
+        #[test]
+        fn should_join_segments_with_configured_delimiter() {
+            let environment = hashmap! {
+                "MyApp_Logging__LogLevel__Default" => "Warning",
+                "MYAPP_AllowedHosts" => "*",
+                "Other_Logging__Console" => "x"
+            };
+
+            assert_contains_exactly_in_any_order(
+                make(environment.clone(), ":").generate_additions(PREFIX),
+                vec![
+                    Property::new("Logging:LogLevel:Default", "Warning"),
+                    Property::new("AllowedHosts", "*"),
+                ],
+            );
+            assert_contains_exactly_in_any_order(
+                make(environment, ".").generate_additions(PREFIX),
+                vec![
+                    Property::new("Logging.LogLevel.Default", "Warning"),
+                    Property::new("AllowedHosts", "*"),
+                ],
+            );
+        }
     }
 }
 
@@ -330,7 +1079,7 @@ impl CustomCaseSensitiveStyleOverrider {
         CustomCaseSensitiveStyleOverrider {
             character_replacement_map: character_replacement_map
                 .iter()
-                .map(|(key, value)| (key.clone(), value.as_ref().to_string()))
+                .map(|(key, value)| (*key, value.as_ref().to_string()))
                 .collect(),
             environment,
         }
@@ -351,12 +1100,12 @@ impl Overrider for CustomCaseSensitiveStyleOverrider {
     fn resolve_substitution(&self, key: &str, prefix: Option<&str>) -> Option<&str> {
         let mut transformed_key: String = prefix.map(|s| s.to_string()).unwrap_or("".to_string());
         for c in key.chars() {
-            transformed_key = transformed_key + self.process_character(c).as_str()
+            transformed_key += self.process_character(c).as_str()
         }
         self.environment.get(transformed_key)
     }
 
-    fn generate_additions(&self, prefix: &str) -> Vec<Property> {
+    fn generate_additions(&self, prefix: &str) -> Vec<Property<'static>> {
         let prefix_match = prefix.to_string();
         let prefixed_entries: HashMap<&str, &str> = self
             .environment
@@ -369,7 +1118,7 @@ impl Overrider for CustomCaseSensitiveStyleOverrider {
         let reverse_replacement_index: HashMap<String, char> = self
             .character_replacement_map
             .iter()
-            .map(|(key, value)| (value.clone(), key.clone()))
+            .map(|(key, value)| (value.clone(), *key))
             .collect();
         let mut replacement_descending: Vec<&str> = reverse_replacement_index
             .keys()
@@ -399,11 +1148,11 @@ impl Overrider for CustomCaseSensitiveStyleOverrider {
                     }
                 }
                 if let Some(existing_key) = found_match {
-                    replaced_key.push(reverse_replacement_index.get(existing_key).unwrap().clone());
-                    start = start + existing_key.len();
+                    replaced_key.push(*reverse_replacement_index.get(existing_key).unwrap());
+                    start += existing_key.len();
                 } else {
                     replaced_key.push_str(prefixless_key[start..start + 1].to_string().as_str());
-                    start = start + 1;
+                    start += 1;
                 }
             }
             properties.push(Property::new(replaced_key.as_str(), value))
@@ -0,0 +1,528 @@
+use crate::document::{removed_duplicates, Document};
+use crate::line_ending::split_physical_lines;
+use crate::model::{DuplicatePolicy, InternalError, LineEnding, Property};
+use crate::properties_parser::{
+    escape_key, escape_value, is_continued, parse_commented_property, parse_line, Line,
+    LogicalLine, LogicalLines,
+};
+use std::borrow::Cow;
+use std::io;
+use std::io::Write;
+
+/// A logical line of a properties document, retaining its exact original text so that it can be
+/// written back unchanged unless the value of its property is replaced. The text is borrowed from
+/// the parsed input until it is modified.
+#[derive(Debug, PartialEq)]
+pub struct Entry<'a> {
+    pub line_num: i32,
+    pub line: Line<'a>,
+    text: Cow<'a, str>,
+    value_start: usize,
+    has_separator: bool,
+}
+
+/// Relocates an error reported by `parse_line` on the content of a logical line onto the
+/// physical line containing the offending character.
+fn locate_in_physical_line(
+    err: InternalError,
+    logical_line: &LogicalLine,
+    content: &str,
+) -> InternalError {
+    match err {
+        InternalError::ParseError {
+            message,
+            snippet: Some(snippet),
+            ..
+        } => {
+            let content_offset = content
+                .char_indices()
+                .nth((snippet.column - 1) as usize)
+                .map(|(i, _)| i)
+                .unwrap_or(content.len());
+            let text = logical_line.text;
+            let text_offset = logical_line.text_offset(content_offset);
+            let physical_lines = logical_line.physical_lines();
+            let mut line_start: usize = 0;
+            let mut physical_index: usize = 0;
+            for (i, physical_line) in physical_lines.iter().enumerate() {
+                physical_index = i;
+                let line_end = line_start + physical_line.len();
+                if text_offset <= line_end {
+                    break;
+                }
+                line_start = line_end
+                    + if text[line_end..].starts_with("\r\n") {
+                        2
+                    } else {
+                        1
+                    };
+            }
+            let column = text[line_start..text_offset].chars().count() + 1;
+            InternalError::parse_error_at(
+                logical_line.line_num + physical_index as i32,
+                column as i32,
+                physical_lines[physical_index],
+                message.as_str(),
+            )
+        }
+        other => other,
+    }
+}
+
+fn property_entry<'a>(line_num: i32, indentation: &str, property: Property<'a>) -> Entry<'a> {
+    let key = escape_key(&property.key);
+    let text = format!("{}{}={}", indentation, key, escape_value(&property.value));
+    Entry {
+        line_num,
+        line: Line::Prop(property),
+        text: Cow::Owned(text),
+        value_start: indentation.len() + key.len() + 1,
+        has_separator: true,
+    }
+}
+
+/// Concrete syntax tree of a properties file: every line is kept verbatim and only the value
+/// span of the properties which are explicitly updated is rewritten.
+#[derive(Debug, PartialEq, Default)]
+pub struct PropertiesDocument<'a> {
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> PropertiesDocument<'a> {
+    /// Parses all the lines of `text`, recovering from errors so that every malformed line is
+    /// reported in a single `InternalError::ParseErrors`. Lines which are not continued are
+    /// borrowed from `text` rather than copied.
+    pub fn parse(text: &'a str) -> Result<PropertiesDocument<'a>, InternalError> {
+        let mut entries: Vec<Entry<'a>> = Vec::new();
+        let mut errors: Vec<InternalError> = Vec::new();
+        for logical_line in LogicalLines::new(text) {
+            let content = logical_line.content();
+            let parsed = match &content {
+                Cow::Borrowed(borrowed) => parse_line(borrowed, logical_line.line_num),
+                Cow::Owned(owned) => parse_line(owned, logical_line.line_num)
+                    .map(|(line, value_offset)| (line.into_owned(), value_offset)),
+            };
+            let (line, value_offset) = match parsed {
+                Ok(parsed) => parsed,
+                Err(err) => {
+                    errors.push(locate_in_physical_line(err, &logical_line, &content));
+                    continue;
+                }
+            };
+            let (value_start, has_separator) = match value_offset {
+                None => (logical_line.text.len(), false),
+                Some(value_offset) => {
+                    // map the start of the value right after the preceding character, so that
+                    // continuation backslashes between separator and value are replaced as well
+                    let value_start = match content[..value_offset.start].chars().last() {
+                        None => 0,
+                        Some(c) => {
+                            logical_line.text_offset(value_offset.start - c.len_utf8())
+                                + c.len_utf8()
+                        }
+                    };
+                    (value_start, value_offset.has_separator)
+                }
+            };
+            entries.push(Entry {
+                line_num: logical_line.line_num,
+                line,
+                text: Cow::Borrowed(logical_line.text),
+                value_start,
+                has_separator,
+            });
+        }
+        if !errors.is_empty() {
+            return Err(InternalError::ParseErrors(errors));
+        }
+        Ok(PropertiesDocument { entries })
+    }
+
+    pub fn entries(&self) -> &Vec<Entry<'a>> {
+        &self.entries
+    }
+}
+
+impl<'a> Document<'a> for PropertiesDocument<'a> {
+    fn properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match &entry.line {
+                Line::Prop(property) => Some((index, property.clone())),
+                Line::Ignorable(_) => None,
+            })
+            .collect()
+    }
+
+    /// Applies `policy` to the properties defined more than once, removing the entries of the
+    /// definitions which are not retained.
+    fn resolve_duplicates(&mut self, policy: DuplicatePolicy) -> Result<(), InternalError> {
+        let definitions = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match &entry.line {
+                Line::Prop(property) => Some((index, property.key.as_ref(), entry.line_num)),
+                Line::Ignorable(_) => None,
+            });
+        let removed = removed_duplicates(definitions, policy)?;
+        let mut index: usize = 0;
+        self.entries.retain(|_| {
+            let keep = !removed.contains(&index);
+            index += 1;
+            keep
+        });
+        Ok(())
+    }
+
+    /// Replaces the value of the property at `index`, leaving key, separator and surrounding
+    /// whitespace untouched. Entries which are not properties are not modified.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError> {
+        let entry = &mut self.entries[index];
+        if let Line::Prop(property) = &mut entry.line {
+            let text = entry.text.to_mut();
+            text.truncate(entry.value_start);
+            if !entry.has_separator {
+                text.push('=');
+                entry.value_start += 1;
+                entry.has_separator = true;
+            }
+            text.push_str(escape_value(value).as_str());
+            property.value = Cow::Owned(value.to_string());
+        }
+        Ok(())
+    }
+
+    /// Properties commented out in comment lines such as '#key=value', with the index of their
+    /// entry.
+    fn commented_properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| match &entry.line {
+                Line::Ignorable(text) => {
+                    parse_commented_property(text).map(|property| (index, property))
+                }
+                Line::Prop(_) => None,
+            })
+            .collect()
+    }
+
+    /// Replaces the entry at `index` with `property` in the `key=value` form, retaining the
+    /// indentation of the original line.
+    fn activate(&mut self, index: usize, property: Property<'a>) -> Result<(), InternalError> {
+        let entry = &self.entries[index];
+        let indentation_len = entry.text.len() - entry.text.trim_start().len();
+        let indentation = entry.text[..indentation_len].to_string();
+        self.entries[index] = property_entry(entry.line_num, &indentation, property);
+        Ok(())
+    }
+
+    /// Appends a new property at the end of the document in the `key=value` form. A continuation
+    /// backslash ending the last property of the input is removed, as it would otherwise continue
+    /// that property onto the new line.
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError> {
+        if let Some(entry) = self.entries.last_mut() {
+            if matches!(entry.line, Line::Prop(_)) && is_continued(&entry.text) {
+                let text = entry.text.to_mut();
+                text.pop();
+                entry.value_start = entry.value_start.min(text.len());
+            }
+        }
+        let line_num = self
+            .entries
+            .last()
+            .map(|entry| entry.line_num + split_physical_lines(&entry.text).len() as i32)
+            .unwrap_or(1);
+        self.entries.push(property_entry(line_num, "", property));
+        Ok(())
+    }
+
+    /// Writes the document terminating every physical line with `line_ending`.
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()> {
+        for entry in &self.entries {
+            if !entry.text.contains(['\n', '\r']) {
+                output.write_all(entry.text.as_bytes())?;
+                output.write_all(line_ending.as_str().as_bytes())?;
+                continue;
+            }
+            for physical_line in split_physical_lines(&entry.text) {
+                output.write_all(physical_line.as_bytes())?;
+                output.write_all(line_ending.as_str().as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod properties_document_tests {
+    use super::*;
+
+    fn parse(s: &str) -> PropertiesDocument<'_> {
+        PropertiesDocument::parse(s).unwrap()
+    }
+
+    fn write(document: &PropertiesDocument) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        document.write(&mut output, LineEnding::Lf).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn parse_should_produce_one_entry_per_logical_line() {
+        let document = parse("# comment\na = b\\\n  c\n\nd:e");
+
+        let lines: Vec<(i32, &Line)> = document
+            .entries()
+            .iter()
+            .map(|e| (e.line_num, &e.line))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (1, &Line::Ignorable("# comment".into())),
+                (2, &Line::Prop(Property::new("a", "bc"))),
+                (4, &Line::Ignorable("".into())),
+                (5, &Line::Prop(Property::new("d", "e"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_should_borrow_text_of_lines_which_are_not_continued() {
+        let document = parse("a=b\r\nc = d\\\r\n  e");
+
+        assert!(matches!(document.entries()[0].text, Cow::Borrowed("a=b")));
+        assert!(matches!(
+            document.entries()[0].line,
+            Line::Prop(Property {
+                key: Cow::Borrowed("a"),
+                value: Cow::Borrowed("b")
+            })
+        ));
+        assert_eq!(
+            document.entries()[1].line,
+            Line::Prop(Property::new("c", "de"))
+        );
+    }
+
+    fn parse_errors(s: &str) -> Vec<InternalError> {
+        match PropertiesDocument::parse(s) {
+            Err(InternalError::ParseErrors(errors)) => errors,
+            _ => panic!("result is not ParseErrors"),
+        }
+    }
+
+    #[test]
+    fn parse_should_report_all_errors() {
+        let errors = parse_errors("a=\\u1\nb=c\nd\\uxyzw=e");
+
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec![
+                "cannot parse property at line 1, column 3: malformed \\uxxxx encoding\na=\\u1\n  ^",
+                "cannot parse property at line 3, column 2: malformed \\uxxxx encoding\nd\\uxyzw=e\n ^",
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_should_report_errors_on_the_physical_line_of_continued_lines() {
+        let errors = parse_errors("a=b\nc=\\\n   x\\u12");
+
+        assert_eq!(
+            errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+            vec!["cannot parse property at line 3, column 5: malformed \\uxxxx encoding\n   x\\u12\n    ^"]
+        );
+    }
+
+    #[test]
+    fn write_should_reproduce_input_exactly() {
+        let input = "# comment\n! other\n  a   :   b\\\n    c\nmy\\ key\\u0041 = \\ value\nkey\n";
+        let document = parse(input.trim_end_matches('\n'));
+
+        assert_eq!(write(&document), input);
+    }
+
+    #[test]
+    fn resolve_duplicates_should_keep_all_definitions_with_keep_all() {
+        let mut document = parse("a=1\nb=2\na=3");
+
+        document
+            .resolve_duplicates(DuplicatePolicy::KeepAll)
+            .unwrap();
+
+        assert_eq!(write(&document), "a=1\nb=2\na=3\n");
+    }
+
+    #[test]
+    fn resolve_duplicates_should_keep_last_definition_with_last_wins() {
+        let mut document = parse("a=1\nb=2\na=3\n# c\na=4");
+
+        document
+            .resolve_duplicates(DuplicatePolicy::LastWins)
+            .unwrap();
+
+        assert_eq!(write(&document), "b=2\n# c\na=4\n");
+    }
+
+    #[test]
+    fn resolve_duplicates_should_keep_first_definition_with_first_wins() {
+        let mut document = parse("a=1\nb=2\na=3\n# c\na=4");
+
+        document
+            .resolve_duplicates(DuplicatePolicy::FirstWins)
+            .unwrap();
+
+        assert_eq!(write(&document), "a=1\nb=2\n# c\n");
+    }
+
+    #[test]
+    fn resolve_duplicates_should_compare_decoded_keys() {
+        let mut document = parse("a\\u0062=1\nab : 2");
+
+        document
+            .resolve_duplicates(DuplicatePolicy::LastWins)
+            .unwrap();
+
+        assert_eq!(write(&document), "ab : 2\n");
+    }
+
+    #[test]
+    fn resolve_duplicates_should_report_both_line_numbers_with_error() {
+        let mut document = parse("a=1\nb=2\\\n  3\na=3");
+
+        match document.resolve_duplicates(DuplicatePolicy::Error) {
+            Err(InternalError::DuplicateKey {
+                key,
+                first_line_num,
+                second_line_num,
+            }) => {
+                assert_eq!(key, "a");
+                assert_eq!(first_line_num, 1);
+                assert_eq!(second_line_num, 4);
+            }
+            _ => panic!("result is not DuplicateKey"),
+        }
+    }
+
+    #[test]
+    fn write_should_use_given_line_ending_for_all_physical_lines() {
+        let document = parse("# comment\na = b\\\n  c");
+        let mut output: Vec<u8> = Vec::new();
+
+        document.write(&mut output, LineEnding::CrLf).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "# comment\r\na = b\\\r\n  c\r\n"
+        );
+    }
+
+    #[test]
+    fn set_value_should_only_replace_value() {
+        let mut document = parse("# comment\n  a   :   b\nc d");
+
+        document.set_value(1, "new value").unwrap();
+        document.set_value(2, " e").unwrap();
+
+        assert_eq!(write(&document), "# comment\n  a   :   new value\nc \\ e\n");
+        assert_eq!(
+            document.entries()[1].line,
+            Line::Prop(Property::new("a", "new value"))
+        );
+    }
+
+    #[test]
+    fn set_value_should_replace_continued_value() {
+        let mut document = parse("a = b\\\n  c\\\n  d\ne=f");
+
+        document.set_value(0, "x").unwrap();
+
+        assert_eq!(write(&document), "a = x\ne=f\n");
+    }
+
+    #[test]
+    fn set_value_should_add_separator_if_missing() {
+        let mut document = parse("key");
+
+        document.set_value(0, "value").unwrap();
+
+        assert_eq!(write(&document), "key=value\n");
+    }
+
+    #[test]
+    fn set_value_should_ignore_non_property_entries() {
+        let mut document = parse("# comment");
+
+        document.set_value(0, "value").unwrap();
+
+        assert_eq!(write(&document), "# comment\n");
+    }
+
+    #[test]
+    fn commented_properties_should_return_properties_in_comments() {
+        let document = parse("# a comment\n#a=1\nb=2\n  ! c : 3");
+
+        assert_eq!(
+            document.commented_properties(),
+            vec![(1, Property::new("a", "1")), (3, Property::new("c", "3")),]
+        );
+    }
+
+    #[test]
+    fn activate_should_replace_comment_with_property_keeping_indentation() {
+        let mut document = parse("# a comment\n  #a=1\nb=2");
+
+        document
+            .activate(1, Property::new("a", "new value"))
+            .unwrap();
+        document.set_value(1, "other value").unwrap();
+
+        assert_eq!(write(&document), "# a comment\n  a=other value\nb=2\n");
+        assert_eq!(
+            document.entries()[1].line,
+            Line::Prop(Property::new("a", "other value"))
+        );
+    }
+
+    #[test]
+    fn push_should_append_escaped_property() {
+        let mut document = parse("a=b\\\n c");
+
+        document.push(Property::new("new key", "value")).unwrap();
+        document.set_value(1, "other").unwrap();
+
+        assert_eq!(write(&document), "a=b\\\n c\nnew\\ key=other\n");
+        assert_eq!(document.entries()[1].line_num, 3);
+    }
+
+    #[test]
+    fn push_should_remove_continuation_ending_input() {
+        let mut document = parse("# c\\\na=1\\\n  2\\");
+
+        document.push(Property::new("b", "2")).unwrap();
+        let output = write(&document);
+
+        assert_eq!(output, "# c\\\na=1\\\n  2\nb=2\n");
+        assert_eq!(
+            parse(&output).properties(),
+            vec![(1, Property::new("a", "12")), (2, Property::new("b", "2"))]
+        );
+    }
+
+    #[test]
+    fn push_should_append_after_continued_last_line() {
+        let mut document = parse("a=1\\");
+
+        document.push(Property::new("b", "2")).unwrap();
+
+        assert_eq!(write(&document), "a=1\nb=2\n");
+        assert_eq!(
+            parse(&write(&document)).properties(),
+            vec![(0, Property::new("a", "1")), (1, Property::new("b", "2"))]
+        );
+    }
+}
@@ -1,44 +1,371 @@
+use crate::line_ending::split_physical_lines;
 use crate::model::{InternalError, Property};
 use regex::Regex;
+use std::borrow::Cow;
+use std::str::CharIndices;
+use std::sync::LazyLock;
 
+static BLANK_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*\n*$").unwrap());
+
+#[derive(Debug, PartialEq)]
+pub enum Line<'a> {
+    Ignorable(Cow<'a, str>),
+    Prop(Property<'a>),
+}
+
+impl Line<'_> {
+    pub fn into_owned(self) -> Line<'static> {
+        match self {
+            Line::Ignorable(text) => Line::Ignorable(Cow::Owned(text.into_owned())),
+            Line::Prop(property) => Line::Prop(property.into_owned()),
+        }
+    }
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t' || c == '\x0c'
+}
+
+fn is_separator(c: char) -> bool {
+    c == '=' || c == ':'
+}
+
+fn is_comment_or_blank(line: &str) -> bool {
+    let content = line.trim_start_matches(is_whitespace);
+    content.is_empty() || content.starts_with("#") || content.starts_with("!")
+}
+
+/// Whether `line` ends with a continuation backslash, an odd number of trailing backslashes.
+pub fn is_continued(line: &str) -> bool {
+    (line.len() - line.trim_end_matches('\\').len()) % 2 == 1
+}
+
+/// A logical line of a properties file, made of one or more physical lines joined by trailing
+/// backslashes.
+#[derive(Debug, PartialEq)]
+pub struct LogicalLine<'a> {
+    pub line_num: i32,
+    /// The original text of the logical line, including the terminators of all its physical
+    /// lines but the last one
+    pub text: &'a str,
+}
+
+impl<'a> LogicalLine<'a> {
+    pub fn physical_lines(&self) -> Vec<&'a str> {
+        split_physical_lines(self.text)
+    }
+
+    /// Calls `f` with the part of every physical line which belongs to the content and its byte
+    /// offset in `text`.
+    fn for_each_content_part<F: FnMut(&'a str, usize)>(&self, mut f: F) {
+        let mut line_start: usize = 0;
+        for (i, line) in self.physical_lines().into_iter().enumerate() {
+            let trimmed = if i == 0 {
+                line
+            } else {
+                line.trim_start_matches(is_whitespace)
+            };
+            let kept = if is_continued(trimmed) {
+                &trimmed[..trimmed.len() - 1]
+            } else {
+                trimmed
+            };
+            f(kept, line_start + line.len() - trimmed.len());
+            let terminator_len = if self.text[line_start + line.len()..].starts_with("\r\n") {
+                2
+            } else {
+                1
+            };
+            line_start += line.len() + terminator_len;
+        }
+    }
+
+    fn is_single_physical_line(&self) -> bool {
+        !self.text.contains(['\n', '\r'])
+            && (is_comment_or_blank(self.text) || !is_continued(self.text))
+    }
+
+    /// Joins the physical lines removing the continuation backslashes and the leading whitespace
+    /// of every continuation line. The content is borrowed from the text for lines which are not
+    /// continued.
+    pub fn content(&self) -> Cow<'a, str> {
+        if self.is_single_physical_line() {
+            return Cow::Borrowed(self.text);
+        }
+        let mut content = String::with_capacity(self.text.len());
+        self.for_each_content_part(|part, _| content.push_str(part));
+        Cow::Owned(content)
+    }
+
+    /// Maps a byte offset in the content to the corresponding byte offset in `text`.
+    pub fn text_offset(&self, content_offset: usize) -> usize {
+        if self.is_single_physical_line() {
+            return content_offset;
+        }
+        let mut result: Option<usize> = None;
+        let mut content_len: usize = 0;
+        self.for_each_content_part(|part, part_start| {
+            if result.is_none() && content_offset < content_len + part.len() {
+                result = Some(part_start + content_offset - content_len);
+            }
+            content_len += part.len();
+        });
+        result.unwrap_or(self.text.len())
+    }
+}
+
+/// Assembles the physical lines of `text` into logical lines following the rules of
+/// `java.util.Properties.load`: lines are terminated by '\n', '\r\n' or '\r', and comment and
+/// blank lines are never continued. Logical lines borrow from `text`, which holds the whole input
+/// rather than a reader so that documents can keep slices of it until they are written.
+pub struct LogicalLines<'a> {
+    text: &'a str,
+    position: usize,
+    line_num: i32,
+}
+
+impl<'a> LogicalLines<'a> {
+    pub fn new(text: &'a str) -> LogicalLines<'a> {
+        LogicalLines {
+            text,
+            position: 0,
+            line_num: 0,
+        }
+    }
+
+    /// Reads the physical line starting at the current position, returning its end and moving
+    /// the position after its terminator.
+    fn next_physical_line(&mut self) -> usize {
+        let start = self.position;
+        self.line_num += 1;
+        match self.text[start..].find(['\n', '\r']) {
+            None => {
+                self.position = self.text.len();
+                self.text.len()
+            }
+            Some(relative_end) => {
+                let end = start + relative_end;
+                self.position = if self.text[end..].starts_with("\r\n") {
+                    end + 2
+                } else {
+                    end + 1
+                };
+                end
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for LogicalLines<'a> {
+    type Item = LogicalLine<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.text.len() {
+            return None;
+        }
+        let start = self.position;
+        let line_num = self.line_num + 1;
+        let mut end = self.next_physical_line();
+        let first = &self.text[start..end];
+        let mut continued = !is_comment_or_blank(first) && is_continued(first);
+        while continued && self.position < self.text.len() {
+            let line_start = self.position;
+            end = self.next_physical_line();
+            continued = is_continued(&self.text[line_start..end]);
+        }
+        Some(LogicalLine {
+            line_num,
+            text: &self.text[start..end],
+        })
+    }
+}
+
+/// A malformed escape sequence: byte offset of its backslash in the raw, still
+/// escaped text and description.
 #[derive(Debug, PartialEq)]
-pub enum Line {
-    Ignorable(String),
-    Prop(Property),
-}
-
-pub fn parse_line(line: &str, line_num: i32) -> Result<Line, InternalError> {
-    if line.starts_with("#") {
-        return Ok(Line::Ignorable(line.trim_end_matches("\n").to_string()));
-    }
-    let empty_line = Regex::new(r"^\s*\n*$").unwrap();
-    if empty_line.is_match(line) {
-        return Ok(Line::Ignorable(line.trim_end_matches("\n").to_string()));
-    }
-    match line.split_once("=") {
-        None => Err(InternalError::parse_error(line_num, "missing '='")),
-        Some((key, value)) => {
-            if key.contains(" ") {
-                return Err(InternalError::parse_error(
-                    line_num,
-                    format!("key '{}' contains spaces", key).as_str(),
-                ));
+pub struct EscapeError {
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+fn read_code_unit(chars: &mut CharIndices, escape_offset: usize) -> Result<u16, EscapeError> {
+    let digits: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+    let malformed = EscapeError {
+        offset: escape_offset,
+        message: "malformed \\uxxxx encoding",
+    };
+    if digits.len() != 4 || !digits.chars().all(|d| d.is_ascii_hexdigit()) {
+        return Err(malformed);
+    }
+    u16::from_str_radix(&digits, 16).map_err(|_| malformed)
+}
+
+/// Decodes the escape sequences supported by `java.util.Properties.load`: '\\uxxxx' (including
+/// surrogate pairs), '\\t', '\\n', '\\r', '\\f' and any other escaped character, which stands for
+/// itself.
+pub fn unescape(text: &str) -> Result<Cow<'_, str>, EscapeError> {
+    if !text.contains('\\') {
+        return Ok(Cow::Borrowed(text));
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.char_indices();
+    while let Some((offset, c)) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            None => {}
+            Some((_, 't')) => result.push('\t'),
+            Some((_, 'n')) => result.push('\n'),
+            Some((_, 'r')) => result.push('\r'),
+            Some((_, 'f')) => result.push('\x0c'),
+            Some((_, 'u')) => {
+                let unit = read_code_unit(&mut chars, offset)?;
+                let mut units = vec![unit];
+                if (0xD800..0xDC00).contains(&unit) && chars.as_str().starts_with("\\u") {
+                    let (low_offset, _) = chars.next().unwrap();
+                    chars.next();
+                    units.push(read_code_unit(&mut chars, low_offset)?);
+                }
+                for decoded in char::decode_utf16(units) {
+                    result.push(decoded.map_err(|_| EscapeError {
+                        offset,
+                        message: "invalid unicode surrogate",
+                    })?);
+                }
             }
-            let value = value.trim_end_matches("\n");
-            Ok(Line::Prop(Property::new(key, value)))
+            Some((_, other)) => result.push(other),
         }
     }
+    Ok(Cow::Owned(result))
+}
+
+fn escape(text: &str, escape_all_spaces: bool, result: &mut String) {
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\x0c' => result.push_str("\\f"),
+            '\t' if i == 0 || escape_all_spaces => result.push_str("\\t"),
+            ' ' if i == 0 || escape_all_spaces => result.push_str("\\ "),
+            '=' | ':' if escape_all_spaces => {
+                result.push('\\');
+                result.push(c);
+            }
+            '#' | '!' if i == 0 && escape_all_spaces => {
+                result.push('\\');
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+    }
+}
+
+/// Escapes a key so that `parse_line` reads it back unchanged: separators, whitespace and a
+/// leading comment character are preceded by a backslash.
+pub fn escape_key(key: &str) -> String {
+    let mut result = String::with_capacity(key.len());
+    escape(key, true, &mut result);
+    result
+}
+
+/// Escapes a value so that `parse_line` reads it back unchanged. Only backslashes, line
+/// terminators and leading whitespace need escaping in a value.
+pub fn escape_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    escape(value, false, &mut result);
+    result
+}
+
+/// Position of the value of a property in the line it was parsed from.
+#[derive(Debug, PartialEq)]
+pub struct ValueOffset {
+    /// Byte offset in the line where the key ends
+    pub key_end: usize,
+    /// Byte offset in the line where the value starts
+    pub start: usize,
+    /// Whether a separator between key and value is present in the line
+    pub has_separator: bool,
+}
+
+/// Parses a single line following the rules of `java.util.Properties.load`: leading whitespace is
+/// ignored, lines starting with '#' or '!' are comments and the key is terminated by the first
+/// unescaped '=', ':' or whitespace character. Whitespace surrounding the separator is discarded
+/// and escape sequences in the key and the value are decoded.
+///
+/// The position of the value is returned alongside properties so that it can be replaced in the
+/// original text.
+pub fn parse_line(
+    line: &str,
+    line_num: i32,
+) -> Result<(Line<'_>, Option<ValueOffset>), InternalError> {
+    let line = line.trim_end_matches(['\n', '\r']);
+    let content = line.trim_start_matches(is_whitespace);
+    if content.starts_with("#") || content.starts_with("!") || BLANK_LINE.is_match(line) {
+        return Ok((Line::Ignorable(Cow::Borrowed(line)), None));
+    }
+    let mut key_end = content.len();
+    let mut preceding_backslash = false;
+    for (i, c) in content.char_indices() {
+        if preceding_backslash {
+            preceding_backslash = false;
+            continue;
+        }
+        if c == '\\' {
+            preceding_backslash = true;
+        } else if is_separator(c) || is_whitespace(c) {
+            key_end = i;
+            break;
+        }
+    }
+    let key_start = line.len() - content.len();
+    let to_parse_error = |start: usize, err: EscapeError| {
+        let column = line[..start + err.offset].chars().count() + 1;
+        InternalError::parse_error_at(line_num, column as i32, line, err.message)
+    };
+    let key = unescape(&content[..key_end]).map_err(|err| to_parse_error(key_start, err))?;
+    let rest = content[key_end..].trim_start_matches(is_whitespace);
+    let rest = rest.strip_prefix(is_separator).unwrap_or(rest);
+    let raw_value = rest.trim_start_matches(is_whitespace);
+    let value_start = line.len() - raw_value.len();
+    let value = unescape(raw_value).map_err(|err| to_parse_error(value_start, err))?;
+    let value_offset = ValueOffset {
+        key_end: key_start + key_end,
+        start: value_start,
+        has_separator: key_end < content.len(),
+    };
+    Ok((Line::Prop(Property { key, value }), Some(value_offset)))
+}
+
+/// Recognises a commented out property such as '#key=value' or '! key: value'. Only comments made
+/// of a key followed by an explicit '=' or ':' separator are considered properties.
+pub fn parse_commented_property(line: &str) -> Option<Property<'_>> {
+    let uncommented = line
+        .trim_start_matches(is_whitespace)
+        .strip_prefix(['#', '!'])?
+        .trim_start_matches(is_whitespace);
+    match parse_line(uncommented, 0) {
+        Ok((Line::Prop(property), Some(value_offset)))
+            if !property.key.is_empty()
+                && uncommented[value_offset.key_end..value_offset.start].contains(is_separator) =>
+        {
+            Some(property)
+        }
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod parse_line_tests {
     use super::*;
-    use crate::model::{InternalError, Property};
+    use crate::model::{InternalError, Property, Snippet};
 
     const LINE_NUM: i32 = 56;
 
-    fn parse(s: &str) -> Result<Line, InternalError> {
-        parse_line(s, LINE_NUM)
+    fn parse(s: &str) -> Result<Line<'_>, InternalError> {
+        parse_line(s, LINE_NUM).map(|(line, _)| line)
     }
 
     fn assert_parse_error_with_message(
@@ -48,20 +375,17 @@ mod parse_line_tests {
         match result {
             Ok(_) => panic!("result is OK, should be parse error"),
             Err(err) => match err {
-                InternalError::ParseError {
-                    line_num: _,
-                    message,
-                } => assert_eq!(message, expected_message),
+                InternalError::ParseError { message, .. } => assert_eq!(message, expected_message),
                 _ => panic!("result is not ParseError"),
             },
         }
     }
 
     #[test]
-    fn should_fail_if_equals_not_present() {
+    fn should_parse_key_without_value_if_separator_not_present() {
         let l = parse("foobar");
 
-        assert_parse_error_with_message(&l, "missing '='");
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("foobar", "")));
     }
 
     #[test]
@@ -86,37 +410,430 @@ mod parse_line_tests {
     }
 
     #[test]
-    fn should_fail_if_key_as_spaces() {
+    fn should_ignore_spaces_around_key() {
         let l = parse("  key  =foobar");
 
-        assert_parse_error_with_message(&l, "key '  key  ' contains spaces");
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("key", "foobar")));
     }
 
     #[test]
-    fn should_retain_spaces_in_value() {
+    fn should_strip_leading_spaces_and_retain_trailing_spaces_in_value() {
         let l = parse("key=  bar foo   ");
 
-        assert_eq!(l.unwrap(), Line::Prop(Property::new("key", "  bar foo   ")));
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("key", "bar foo   ")));
+    }
+
+    #[test]
+    fn should_strip_carriage_return_at_end_of_value() {
+        let l = parse("key1=value1\r\n");
+
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("key1", "value1")));
+    }
+
+    #[test]
+    fn should_separate_key_from_value_with_colon() {
+        let l = parse("key: value");
+
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("key", "value")));
+    }
+
+    #[test]
+    fn should_separate_key_from_value_with_whitespace() {
+        let l = parse("key \t value with spaces");
+
+        assert_eq!(
+            l.unwrap(),
+            Line::Prop(Property::new("key", "value with spaces"))
+        );
+    }
+
+    #[test]
+    fn should_consume_only_first_separator_after_whitespace() {
+        let l = parse("key = :value=");
+
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("key", ":value=")));
+    }
+
+    #[test]
+    fn should_not_terminate_key_on_escaped_separators() {
+        let l = parse("my\\ key\\:a\\=b=value");
+
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("my key:a=b", "value")));
+    }
+
+    #[test]
+    fn should_fail_on_malformed_unicode_escape() {
+        let l = parse("key=\\u12g4");
+
+        assert_parse_error_with_message(&l, "malformed \\uxxxx encoding");
+    }
+
+    #[test]
+    fn should_fail_on_signed_unicode_escape() {
+        let l = parse("key=\\u+abc");
+
+        assert_parse_error_with_message(&l, "malformed \\uxxxx encoding");
+    }
+
+    #[test]
+    fn should_fail_on_truncated_unicode_escape() {
+        let l = parse("key\\u12=value");
+
+        assert_parse_error_with_message(&l, "malformed \\uxxxx encoding");
+    }
+
+    #[test]
+    fn should_report_column_and_line_of_malformed_escape() {
+        let l = parse("  k\\u00e0y = v\\u12g4");
+
+        match l {
+            Err(InternalError::ParseError {
+                line_num, snippet, ..
+            }) => {
+                assert_eq!(line_num, LINE_NUM);
+                assert_eq!(
+                    snippet,
+                    Some(Snippet {
+                        column: 15,
+                        line: "  k\\u00e0y = v\\u12g4".to_string()
+                    })
+                );
+            }
+            _ => panic!("result is not ParseError"),
+        }
+    }
+
+    #[test]
+    fn should_report_column_of_malformed_escape_in_key() {
+        let l = parse("k\\uzzzz=v");
+
+        match l {
+            Err(InternalError::ParseError {
+                snippet: Some(snippet),
+                ..
+            }) => assert_eq!(snippet.column, 2),
+            _ => panic!("result is not ParseError with snippet"),
+        }
+    }
+
+    #[test]
+    fn should_accept_escaped_backslash_followed_by_u() {
+        let l = parse("key=\\\\u12");
+
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("key", "\\u12")));
+    }
+
+    #[test]
+    fn should_decode_escape_sequences_in_key_and_value() {
+        let l = parse("k\\u0065y\\t=\\ v\\u00e0l\\nu\\re\\f\\x");
+
+        assert_eq!(
+            l.unwrap(),
+            Line::Prop(Property::new("key\t", " v\u{e0}l\nu\re\x0cx"))
+        );
+    }
+
+    #[test]
+    fn should_decode_surrogate_pairs() {
+        let l = parse("key=\\ud83d\\ude00");
+
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("key", "\u{1f600}")));
+    }
+
+    #[test]
+    fn should_fail_on_unpaired_surrogate() {
+        let l = parse("key=\\ud83d");
+
+        assert_parse_error_with_message(&l, "invalid unicode surrogate");
+    }
+
+    #[test]
+    fn should_return_value_offset_after_separator() {
+        let result = parse_line("  key = value", LINE_NUM).unwrap();
+
+        assert_eq!(
+            result.1,
+            Some(ValueOffset {
+                key_end: 5,
+                start: 8,
+                has_separator: true
+            })
+        );
+    }
+
+    #[test]
+    fn should_return_value_offset_without_separator() {
+        let result = parse_line("key", LINE_NUM).unwrap();
+
+        assert_eq!(
+            result.1,
+            Some(ValueOffset {
+                key_end: 3,
+                start: 3,
+                has_separator: false
+            })
+        );
+    }
+
+    #[test]
+    fn should_not_return_value_offset_for_ignorable_lines() {
+        let result = parse_line("# key=value", LINE_NUM).unwrap();
+
+        assert_eq!(result.1, None);
+    }
+
+    #[test]
+    fn should_parse_empty_key() {
+        let l = parse("=value");
+
+        assert_eq!(l.unwrap(), Line::Prop(Property::new("", "value")));
     }
 
     #[test]
     fn should_ignore_empty_lines_and_trim_newlines() {
         let l = parse("\n\n");
 
-        assert_eq!(l.unwrap(), Line::Ignorable("".to_string()));
+        assert_eq!(l.unwrap(), Line::Ignorable("".into()));
     }
 
     #[test]
     fn should_ignore_lines_with_spaces_and_tabs_trim_newlines() {
         let l = parse("  \t  \n\n");
 
-        assert_eq!(l.unwrap(), Line::Ignorable("  \t  ".to_string()));
+        assert_eq!(l.unwrap(), Line::Ignorable("  \t  ".into()));
     }
 
     #[test]
     fn should_ignore_comment_lines() {
         let l = parse("# abc\n\n");
 
-        assert_eq!(l.unwrap(), Line::Ignorable("# abc".to_string()));
+        assert_eq!(l.unwrap(), Line::Ignorable("# abc".into()));
+    }
+
+    #[test]
+    fn should_ignore_exclamation_mark_comment_lines() {
+        let l = parse("! abc");
+
+        assert_eq!(l.unwrap(), Line::Ignorable("! abc".into()));
+    }
+
+    #[test]
+    fn should_ignore_indented_comment_lines() {
+        let l = parse(" \t# abc");
+
+        assert_eq!(l.unwrap(), Line::Ignorable(" \t# abc".into()));
+    }
+}
+
+#[cfg(test)]
+mod logical_lines_tests {
+    use super::*;
+
+    fn logical_lines(s: &str) -> Vec<(i32, &str)> {
+        LogicalLines::new(s).map(|l| (l.line_num, l.text)).collect()
+    }
+
+    fn logical_line(line_num: i32, text: &str) -> LogicalLine<'_> {
+        LogicalLine { line_num, text }
+    }
+
+    #[test]
+    fn should_produce_one_logical_line_per_physical_line_without_continuations() {
+        let result = logical_lines("a=b\n# comment\nc=d\n");
+
+        assert_eq!(result, vec![(1, "a=b"), (2, "# comment"), (3, "c=d")]);
+    }
+
+    #[test]
+    fn should_split_on_all_line_terminators() {
+        let result = logical_lines("a=b\r\n\r\nc=d\re=f");
+
+        assert_eq!(result, vec![(1, "a=b"), (2, ""), (3, "c=d"), (4, "e=f")]);
+    }
+
+    #[test]
+    fn should_join_lines_ending_with_backslash() {
+        let result = logical_lines("a=b\\\n  c\\\r\n  d\ne=f");
+
+        assert_eq!(result, vec![(1, "a=b\\\n  c\\\r\n  d"), (4, "e=f")]);
+    }
+
+    #[test]
+    fn should_not_join_lines_ending_with_escaped_backslash() {
+        let result = logical_lines("a=b\\\\\nc=d");
+
+        assert_eq!(result, vec![(1, "a=b\\\\"), (2, "c=d")]);
+    }
+
+    #[test]
+    fn should_not_continue_comment_lines() {
+        let result = logical_lines("# comment \\\nc=d");
+
+        assert_eq!(result, vec![(1, "# comment \\"), (2, "c=d")]);
+    }
+
+    #[test]
+    fn should_include_comment_like_continuation_lines() {
+        let result = logical_lines("a=b\\\n# c");
+
+        assert_eq!(result, vec![(1, "a=b\\\n# c")]);
+    }
+
+    #[test]
+    fn should_stop_at_end_of_input_on_dangling_continuation() {
+        let result = logical_lines("a=b\\\n");
+
+        assert_eq!(result, vec![(1, "a=b\\")]);
+    }
+
+    #[test]
+    fn physical_lines_should_split_text_on_terminators() {
+        let line = logical_line(1, "a=b\\\r\n  c\\\n");
+
+        assert_eq!(line.physical_lines(), vec!["a=b\\", "  c\\", ""]);
+    }
+
+    #[test]
+    fn content_should_strip_backslashes_and_leading_whitespace_of_continuation_lines() {
+        let line = logical_line(1, "key = first \\\n   second\\\r\n\tthird");
+
+        assert_eq!(line.content(), "key = first secondthird");
+    }
+
+    #[test]
+    fn content_should_borrow_text_of_single_physical_lines() {
+        let line = logical_line(1, "key=value");
+
+        assert!(matches!(line.content(), Cow::Borrowed("key=value")));
+    }
+
+    #[test]
+    fn content_should_keep_comment_lines_unchanged() {
+        let line = logical_line(1, "# comment \\");
+
+        assert_eq!(line.content(), "# comment \\");
+    }
+
+    #[test]
+    fn content_should_strip_dangling_continuation() {
+        let line = logical_line(1, "key=value\\");
+
+        assert_eq!(line.content(), "key=value");
+    }
+
+    #[test]
+    fn text_offset_should_map_content_to_text_positions() {
+        let line = logical_line(1, "a=b\\\r\n  c\\\nd");
+
+        let offsets: Vec<usize> = (0..=5).map(|i| line.text_offset(i)).collect();
+
+        assert_eq!(line.content(), "a=bcd");
+        assert_eq!(offsets, vec![0, 1, 2, 8, 11, 12]);
+    }
+
+    #[test]
+    fn text_offset_should_be_identity_for_single_physical_lines() {
+        let line = logical_line(1, "a=b");
+
+        assert_eq!(line.text_offset(2), 2);
+    }
+}
+
+#[cfg(test)]
+mod escape_tests {
+    use super::*;
+
+    #[test]
+    fn escape_key_should_escape_separators_whitespace_and_backslashes() {
+        assert_eq!(escape_key("my key:a=b\\c\td"), "my\\ key\\:a\\=b\\\\c\\td");
+    }
+
+    #[test]
+    fn escape_key_should_escape_leading_comment_characters_only() {
+        assert_eq!(escape_key("#a#"), "\\#a#");
+        assert_eq!(escape_key("!a!"), "\\!a!");
+    }
+
+    #[test]
+    fn escape_value_should_escape_only_leading_whitespace() {
+        assert_eq!(escape_value("  a b\t"), "\\  a b\t");
+        assert_eq!(escape_value("\ta"), "\\ta");
+    }
+
+    #[test]
+    fn escape_value_should_not_escape_separators() {
+        assert_eq!(escape_value("http://host:80/?a=b"), "http://host:80/?a=b");
+    }
+
+    #[test]
+    fn escape_value_should_escape_line_terminators_and_backslashes() {
+        assert_eq!(escape_value("a\nb\rc\x0cd\\"), "a\\nb\\rc\\fd\\\\");
+    }
+
+    #[test]
+    fn escaped_key_and_value_should_round_trip_through_parse_line() {
+        for (key, value) in [
+            ("my key", " value"),
+            ("a=b", "c:d"),
+            ("#comment", "!bang"),
+            ("tab\tkey", "\tline\nbreak\\"),
+        ] {
+            let line = format!("{}={}", escape_key(key), escape_value(value));
+
+            assert_eq!(
+                parse_line(&line, 1).unwrap().0,
+                Line::Prop(Property::new(key, value))
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod parse_commented_property_tests {
+    use super::*;
+
+    #[test]
+    fn should_recognise_commented_property() {
+        assert_eq!(
+            parse_commented_property("#key=value"),
+            Some(Property::new("key", "value"))
+        );
+    }
+
+    #[test]
+    fn should_recognise_indented_and_spaced_commented_property() {
+        assert_eq!(
+            parse_commented_property("  #  log.dirs : /tmp/kafka"),
+            Some(Property::new("log.dirs", "/tmp/kafka"))
+        );
+        assert_eq!(
+            parse_commented_property("!key = value"),
+            Some(Property::new("key", "value"))
+        );
+    }
+
+    #[test]
+    fn should_decode_escapes_in_commented_property() {
+        assert_eq!(
+            parse_commented_property("#my\\ key=\\u00e0"),
+            Some(Property::new("my key", "\u{e0}"))
+        );
+    }
+
+    #[test]
+    fn should_not_recognise_prose_comments() {
+        assert_eq!(parse_commented_property("# This is a comment"), None);
+        assert_eq!(parse_commented_property("#"), None);
+        assert_eq!(parse_commented_property("# =value"), None);
+    }
+
+    #[test]
+    fn should_not_recognise_escaped_separator_as_explicit() {
+        assert_eq!(parse_commented_property("#key\\= value"), None);
+    }
+
+    #[test]
+    fn should_not_recognise_active_properties() {
+        assert_eq!(parse_commented_property("key=value"), None);
     }
 }
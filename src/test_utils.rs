@@ -33,24 +33,24 @@ pub fn assert_contains_exactly_in_any_order<T: PartialEq + Debug, V: AsRef<Vec<T
     let mut result: String = "".to_string();
     let comma_separator = ", ";
     if !unmatched_indexes_in_expected.is_empty() {
-        result = result + "The following elements where expected but not found:\n";
+        result += "The following elements where expected but not found:\n";
         let mut separator = "[";
         for i in unmatched_indexes_in_expected.iter() {
             result = result + separator + format!("{:?}", expected.as_ref()[*i]).as_str();
             separator = comma_separator;
         }
-        result = result + "]\n"
+        result += "]\n"
     }
     if !excess.is_empty() {
-        result = result + "The following elements where not expected:\n";
+        result += "The following elements where not expected:\n";
         let mut separator = "[";
         for a in excess {
             result = result + separator + format!("{:?}", actual.as_ref()[a]).as_str();
             separator = comma_separator;
         }
-        result = result + "]";
+        result += "]";
     }
-    if result != "" {
+    if !result.is_empty() {
         panic!("{}", result);
     }
 }
@@ -0,0 +1,457 @@
+use crate::document::Document;
+use crate::keys::{key_segments, segments_key, Segment};
+use crate::line_ending::split_physical_lines;
+use crate::model::{InternalError, LineEnding, Property};
+use std::borrow::Cow;
+use std::io;
+use std::io::Write;
+use toml_edit::{
+    Array, ArrayOfTables, Datetime, DocumentMut, InlineTable, Item, Table, TableLike, Value,
+};
+
+/// A scalar value of the document with the path it is reachable with.
+#[derive(Debug)]
+struct Entry {
+    path: Vec<Segment>,
+    key: String,
+    value: String,
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.value().clone(),
+        Value::Integer(integer) => integer.display_repr().into_owned(),
+        Value::Float(float) => float.display_repr().into_owned(),
+        Value::Boolean(boolean) => boolean.display_repr().into_owned(),
+        Value::Datetime(datetime) => datetime.display_repr().into_owned(),
+        Value::Array(_) | Value::InlineTable(_) => unreachable!("not a scalar"),
+    }
+}
+
+fn flatten_table(table: &dyn TableLike, path: &mut Vec<Segment>, entries: &mut Vec<Entry>) {
+    for (key, item) in table.iter() {
+        path.push(Segment::Key(key.to_string()));
+        flatten_item(item, path, entries);
+        path.pop();
+    }
+}
+
+fn flatten_item(item: &Item, path: &mut Vec<Segment>, entries: &mut Vec<Entry>) {
+    match item {
+        Item::None => {}
+        Item::Value(value) => flatten_value(value, path, entries),
+        Item::Table(table) => flatten_table(table, path, entries),
+        Item::ArrayOfTables(array) => {
+            for (index, table) in array.iter().enumerate() {
+                path.push(Segment::Index(index));
+                flatten_table(table, path, entries);
+                path.pop();
+            }
+        }
+    }
+}
+
+fn flatten_value(value: &Value, path: &mut Vec<Segment>, entries: &mut Vec<Entry>) {
+    match value {
+        Value::Array(array) => {
+            for (index, element) in array.iter().enumerate() {
+                path.push(Segment::Index(index));
+                flatten_value(element, path, entries);
+                path.pop();
+            }
+        }
+        Value::InlineTable(table) => flatten_table(table, path, entries),
+        scalar => entries.push(Entry {
+            path: path.clone(),
+            key: segments_key(path),
+            value: scalar_text(scalar),
+        }),
+    }
+}
+
+fn value_at_mut<'d>(item: &'d mut Item, path: &[Segment]) -> Option<&'d mut Value> {
+    match (item, path.split_first()) {
+        (Item::Value(value), _) => nested_value_at_mut(value, path),
+        (Item::Table(table), Some((Segment::Key(key), rest))) => {
+            value_at_mut(table.get_mut(key)?, rest)
+        }
+        (Item::ArrayOfTables(array), Some((Segment::Index(index), rest))) => {
+            let (Segment::Key(key), rest) = rest.split_first()? else {
+                return None;
+            };
+            value_at_mut(array.get_mut(*index)?.get_mut(key)?, rest)
+        }
+        _ => None,
+    }
+}
+
+fn nested_value_at_mut<'d>(value: &'d mut Value, path: &[Segment]) -> Option<&'d mut Value> {
+    match (value, path.split_first()) {
+        (value, None) => Some(value),
+        (Value::Array(array), Some((Segment::Index(index), rest))) => {
+            nested_value_at_mut(array.get_mut(*index)?, rest)
+        }
+        (Value::InlineTable(table), Some((Segment::Key(key), rest))) => {
+            nested_value_at_mut(table.get_mut(key)?, rest)
+        }
+        _ => None,
+    }
+}
+
+/// Converts `text` to a value of the same type as `current` when it represents one, otherwise to
+/// a string.
+fn typed_value(text: &str, current: &Value) -> Value {
+    let typed = match current {
+        Value::Integer(_) => text.parse::<i64>().ok().map(Value::from),
+        Value::Float(_) => text.parse::<f64>().ok().map(Value::from),
+        Value::Boolean(_) => text.parse::<bool>().ok().map(Value::from),
+        Value::Datetime(_) => text.parse::<Datetime>().ok().map(Value::from),
+        _ => None,
+    };
+    typed.unwrap_or_else(|| Value::from(text))
+}
+
+/// Converts `text` to an integer or a boolean when it is written exactly as one, otherwise to a
+/// string.
+fn inferred_value(text: &str) -> Value {
+    let integer = text
+        .parse::<i64>()
+        .ok()
+        .filter(|integer| integer.to_string() == text);
+    match (integer, text) {
+        (Some(integer), _) => Value::from(integer),
+        (None, "true") => Value::from(true),
+        (None, "false") => Value::from(false),
+        _ => Value::from(text),
+    }
+}
+
+/// A new container for the segment following `next`: tables for keys and arrays for indexes,
+/// which are arrays of tables when followed by further keys outside inline tables.
+fn new_container(next: &Segment, nested: bool, inline: bool) -> Item {
+    match (next, inline) {
+        (Segment::Key(_), true) => Item::Value(Value::InlineTable(InlineTable::new())),
+        (Segment::Key(_), false) => {
+            let mut table = Table::new();
+            table.set_implicit(true);
+            Item::Table(table)
+        }
+        (Segment::Index(_), false) if nested => Item::ArrayOfTables(ArrayOfTables::new()),
+        (Segment::Index(_), _) => Item::Value(Value::Array(Array::new())),
+    }
+}
+
+/// Inserts `value` at `path` under `table`, creating the missing tables and arrays. Returns false
+/// if the path crosses an existing value.
+fn insert_into_table(table: &mut dyn TableLike, path: &[Segment], value: Value) -> bool {
+    let Some((Segment::Key(key), rest)) = path.split_first() else {
+        return false;
+    };
+    if rest.is_empty() {
+        if table.contains_key(key) {
+            return false;
+        }
+        table.insert(key, Item::Value(value));
+        return true;
+    }
+    if !table.contains_key(key) {
+        let mut container = new_container(&rest[0], rest.len() > 1, false);
+        if let Item::Table(new_table) = &mut container {
+            // keep dotted keys such as 'a.b = 1' in the dotted form
+            new_table.set_dotted(table.is_dotted());
+        }
+        table.insert(key, container);
+    }
+    insert_into_item(table.get_mut(key).unwrap(), rest, value)
+}
+
+fn insert_into_item(item: &mut Item, path: &[Segment], value: Value) -> bool {
+    match item {
+        Item::Table(table) => insert_into_table(table, path, value),
+        Item::Value(existing) => insert_into_value(existing, path, value),
+        Item::ArrayOfTables(array) => {
+            let Some((Segment::Index(index), rest)) = path.split_first() else {
+                return false;
+            };
+            if *index >= array.len() {
+                array.push(Table::new());
+            }
+            let index = (*index).min(array.len() - 1);
+            insert_into_table(array.get_mut(index).unwrap(), rest, value)
+        }
+        Item::None => false,
+    }
+}
+
+fn insert_into_value(existing: &mut Value, path: &[Segment], value: Value) -> bool {
+    match (existing, path.split_first()) {
+        (Value::InlineTable(table), Some((Segment::Key(key), rest))) => {
+            if rest.is_empty() {
+                if table.contains_key(key) {
+                    return false;
+                }
+                table.insert(key, value);
+                return true;
+            }
+            if !table.contains_key(key) {
+                if let Item::Value(container) = new_container(&rest[0], rest.len() > 1, true) {
+                    table.insert(key, container);
+                }
+            }
+            insert_into_value(table.get_mut(key).unwrap(), rest, value)
+        }
+        (Value::Array(array), Some((Segment::Index(index), rest))) => {
+            if rest.is_empty() {
+                if *index < array.len() {
+                    return false;
+                }
+                array.push(value);
+                return true;
+            }
+            if *index >= array.len() {
+                if let Item::Value(container) = new_container(&rest[0], rest.len() > 1, true) {
+                    array.push(container);
+                }
+            }
+            let index = (*index).min(array.len() - 1);
+            insert_into_value(array.get_mut(index).unwrap(), rest, value)
+        }
+        _ => false,
+    }
+}
+
+/// A TOML document whose values are addressed by flattened keys: keys of tables, inline tables
+/// and dotted keys are joined with '.' and the elements of arrays and arrays of tables are
+/// addressed as '[n]'. Formatting and comments are retained by editing the document in place.
+#[derive(Debug)]
+pub struct TomlDocument {
+    document: DocumentMut,
+    entries: Vec<Entry>,
+}
+
+impl TomlDocument {
+    pub fn parse(text: &str) -> Result<TomlDocument, InternalError> {
+        let document = text.parse::<DocumentMut>().map_err(|err| {
+            let offset = err.span().map(|span| span.start).unwrap_or(0);
+            InternalError::parse_error_at_offset(text, offset, err.message().trim_end())
+        })?;
+        let mut entries: Vec<Entry> = Vec::new();
+        flatten_table(document.as_table(), &mut Vec::new(), &mut entries);
+        Ok(TomlDocument { document, entries })
+    }
+}
+
+impl<'a> Document<'a> for TomlDocument {
+    fn properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                (
+                    index,
+                    Property {
+                        key: Cow::Borrowed(entry.key.as_str()),
+                        value: Cow::Borrowed(entry.value.as_str()),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Replaces the value at `index` keeping its type when `value` represents one of the same
+    /// type, as well as the comments and whitespace surrounding it.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError> {
+        let entry = &mut self.entries[index];
+        if let Some(current) = value_at_mut(self.document.as_item_mut(), &entry.path) {
+            let mut replacement = typed_value(value, current);
+            *replacement.decor_mut() = current.decor().clone();
+            *current = replacement;
+        }
+        entry.value = value.to_string();
+        Ok(())
+    }
+
+    /// Inserts the property into its table, creating the missing tables. Keys crossing an
+    /// existing value are added to the root table as quoted keys.
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError> {
+        let value = inferred_value(&property.value);
+        let segments = key_segments(&property.key);
+        if !insert_into_table(self.document.as_table_mut(), &segments, value.clone()) {
+            self.document.insert(&property.key, Item::Value(value));
+        }
+        Ok(())
+    }
+
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()> {
+        let rendered = self.document.to_string();
+        let mut lines = split_physical_lines(&rendered);
+        if lines.last() == Some(&"") {
+            lines.pop();
+        }
+        for line in lines {
+            output.write_all(line.as_bytes())?;
+            output.write_all(line_ending.as_str().as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod toml_document_tests {
+    use super::*;
+
+    fn parse(s: &str) -> TomlDocument {
+        TomlDocument::parse(s).unwrap()
+    }
+
+    fn properties(document: &TomlDocument) -> Vec<(String, String)> {
+        Document::properties(document)
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect()
+    }
+
+    fn write(document: &TomlDocument) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        Document::write(document, &mut output, LineEnding::Lf).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn parse_should_flatten_tables_arrays_and_dotted_keys() {
+        let document = parse(
+            "title = \"app\"\nsite.name = 'x'\n\n[server]\nport = 8080\nratio = 1.50\nhosts = [\"a\", \"b\"]\ntls = { enabled = true }\n\n[[users]]\nname = \"u\"\n",
+        );
+
+        assert_eq!(
+            properties(&document),
+            pairs(&[
+                ("title", "app"),
+                ("site.name", "x"),
+                ("server.port", "8080"),
+                ("server.ratio", "1.50"),
+                ("server.hosts[0]", "a"),
+                ("server.hosts[1]", "b"),
+                ("server.tls.enabled", "true"),
+                ("users[0].name", "u"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_should_report_errors_with_position() {
+        let error = TomlDocument::parse("a = 1\nb = = 2\n").unwrap_err();
+
+        match error {
+            InternalError::ParseError {
+                line_num, snippet, ..
+            } => {
+                assert_eq!(line_num, 2);
+                assert_eq!(snippet.unwrap().line, "b = = 2");
+            }
+            _ => panic!("error is not ParseError"),
+        }
+    }
+
+    #[test]
+    fn write_should_reproduce_input_exactly() {
+        let input = "# comment\ntitle = \"app\" # trailing\n\n[server]\n  port   = 8080\nhosts = ['a', \"b\"]\n";
+        let document = parse(input);
+
+        assert_eq!(write(&document), input);
+    }
+
+    #[test]
+    fn write_should_use_given_line_ending() {
+        let document = parse("[a]\r\nb = 1\r\n");
+        let mut output: Vec<u8> = Vec::new();
+
+        Document::write(&document, &mut output, LineEnding::CrLf).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "[a]\r\nb = 1\r\n");
+    }
+
+    #[test]
+    fn set_value_should_keep_type_and_comments() {
+        let mut document = parse(
+            "[server]\nport = 8080 # port\nenabled = false\nname = \"x\"\nhosts = [\"a\", \"b\"]\nother = 1\n",
+        );
+
+        document.set_value(0, "9090").unwrap();
+        document.set_value(1, "true").unwrap();
+        document.set_value(2, "y").unwrap();
+        document.set_value(4, "c").unwrap();
+        document.set_value(5, "not a number").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "[server]\nport = 9090 # port\nenabled = true\nname = \"y\"\nhosts = [\"a\", \"c\"]\nother = \"not a number\"\n"
+        );
+        assert_eq!(
+            properties(&document)[0],
+            ("server.port".to_string(), "9090".to_string())
+        );
+    }
+
+    #[test]
+    fn push_should_insert_into_existing_and_new_tables() {
+        let mut document = parse("a.b = 1\n\n[server]\nport = 8080\n\n[other]\nx = 1\n");
+
+        document
+            .push(Property::new("server.host", "localhost"))
+            .unwrap();
+        document
+            .push(Property::new("server.ssl.enabled", "true"))
+            .unwrap();
+        document.push(Property::new("a.c", "2")).unwrap();
+        document
+            .push(Property::new("logging.level.root", "info"))
+            .unwrap();
+
+        assert_eq!(
+            write(&document),
+            "a.b = 1\na.c = 2\n\n[server]\nport = 8080\nhost = \"localhost\"\n\n[server.ssl]\nenabled = true\n\n[other]\nx = 1\n\n[logging.level]\nroot = \"info\"\n"
+        );
+    }
+
+    #[test]
+    fn push_should_append_to_arrays() {
+        let mut document = parse("hosts = [\"a\"]\n\n[[users]]\nname = \"u\"\n");
+
+        document.push(Property::new("hosts[1]", "b")).unwrap();
+        document.push(Property::new("users[1].name", "v")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "hosts = [\"a\", \"b\"]\n\n[[users]]\nname = \"u\"\n\n[[users]]\nname = \"v\"\n"
+        );
+    }
+
+    #[test]
+    fn push_should_add_keys_nested_under_values_as_quoted_keys() {
+        let mut document = parse("a = 1\n");
+
+        document.push(Property::new("a.b", "x")).unwrap();
+
+        assert_eq!(write(&document), "a = 1\n\"a.b\" = \"x\"\n");
+        assert_eq!(
+            properties(&parse(write(&document).as_str()))[1],
+            ("a.b".to_string(), "x".to_string())
+        );
+    }
+
+    #[test]
+    fn inferred_value_should_only_infer_exact_integers_and_booleans() {
+        assert_eq!(inferred_value("42").to_string(), "42");
+        assert_eq!(inferred_value("true").to_string(), "true");
+        assert_eq!(inferred_value("007").to_string(), "\"007\"");
+        assert_eq!(inferred_value("1.10").to_string(), "\"1.10\"");
+    }
+}
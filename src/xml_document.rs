@@ -0,0 +1,746 @@
+use crate::document::{removed_duplicates, Document};
+use crate::line_ending::{detect_line_ending, split_physical_lines};
+use crate::model::{DuplicatePolicy, Encoding, InternalError, LineEnding, Property};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::io::Write;
+
+/// Prolog written when generating a document from an empty input, as `Properties.storeToXML`
+/// does.
+const PROLOG: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n<!DOCTYPE properties SYSTEM \"http://java.sun.com/dtd/properties.dtd\">\n";
+
+/// An '<entry>' element, with the location of its parts in the text.
+#[derive(Debug)]
+struct XmlEntry {
+    key: String,
+    value: String,
+    line_num: i32,
+    /// Byte offset where the whitespace preceding the element starts
+    leading: usize,
+    /// Byte offset where the content starts, or where the element starts if self-closing
+    content_start: usize,
+    /// Byte offset where the content ends, or where the element ends if self-closing
+    content_end: usize,
+    /// Byte offset where the element ends
+    end: usize,
+    self_closing: bool,
+}
+
+/// Where added entries are inserted in the '<properties>' element.
+#[derive(Debug)]
+enum Insertion {
+    /// After the last child, or after the start tag when empty
+    After(usize),
+    /// Replacing a self-closing '<properties/>' element
+    Expand(usize, usize),
+    /// No '<properties>' element, since the input is blank
+    NewDocument,
+}
+
+fn escape(text: &str, attribute: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '\r' => escaped.push_str("&#13;"),
+            '\n' if attribute => escaped.push_str("&#10;"),
+            '\t' if attribute => escaped.push_str("&#9;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn entry_element(key: &str, value: &str) -> String {
+    format!(
+        "<entry key=\"{}\">{}</entry>",
+        escape(key, true),
+        escape(value, false)
+    )
+}
+
+/// The byte range of the 'encoding' pseudo-attribute of `declaration`, if present.
+fn encoding_attribute(declaration: &str) -> Option<(usize, usize)> {
+    let start = declaration.find("encoding")?;
+    let quote_start = start + declaration[start..].find(['"', '\''])?;
+    let quote = &declaration[quote_start..quote_start + 1];
+    let quote_end = quote_start + 1 + declaration[quote_start + 1..].find(quote)?;
+    Some((start, quote_end + 1))
+}
+
+/// Names `encoding` in the XML declaration of `text`, replacing the declared encoding or adding
+/// a declaration when the text has none and is not UTF-8, the default of XML documents.
+pub fn declare_encoding(text: &str, encoding: Encoding) -> Cow<'_, str> {
+    let attribute = format!("encoding=\"{}\"", encoding.name());
+    let declaration = text
+        .strip_prefix("<?xml")
+        .filter(|rest| rest.starts_with(char::is_whitespace))
+        .and_then(|_| text.find("?>"))
+        .map(|end| &text[..end]);
+    let Some(declaration) = declaration else {
+        if encoding == Encoding::Utf8 {
+            return Cow::Borrowed(text);
+        }
+        let line_ending = detect_line_ending(text);
+        return Cow::Owned(format!(
+            "<?xml version=\"1.0\" {}?>{}{}",
+            attribute,
+            line_ending.as_str(),
+            text
+        ));
+    };
+    let (start, end) = encoding_attribute(declaration).unwrap_or_else(|| {
+        // the encoding precedes the standalone pseudo-attribute
+        let position = declaration
+            .find("standalone")
+            .unwrap_or(declaration.trim_end().len());
+        (position, position)
+    });
+    let attribute = if start == end {
+        if declaration[..start].ends_with(char::is_whitespace) {
+            format!("{} ", attribute)
+        } else {
+            format!(" {}", attribute)
+        }
+    } else {
+        attribute
+    };
+    Cow::Owned(format!("{}{}{}", &text[..start], attribute, &text[end..]))
+}
+
+struct XmlParser<'t> {
+    text: &'t str,
+    position: usize,
+}
+
+impl XmlParser<'_> {
+    fn error(&self, message: &str) -> InternalError {
+        InternalError::parse_error_at_offset(self.text, self.position, message)
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skips past `terminator`, failing with `message` if it is never found.
+    fn skip_past(&mut self, terminator: &str, message: &str) -> Result<(), InternalError> {
+        match self.rest().find(terminator) {
+            Some(index) => {
+                self.position += index + terminator.len();
+                Ok(())
+            }
+            None => Err(self.error(message)),
+        }
+    }
+
+    /// Skips whitespace, comments, processing instructions and document type declarations.
+    fn skip_misc(&mut self) -> Result<(), InternalError> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>", "unterminated processing instruction")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->", "unterminated comment")?;
+            } else if self.rest().starts_with("<!DOCTYPE") {
+                let mut depth = 0;
+                let end = self.rest().find(|c: char| {
+                    match c {
+                        '[' => depth += 1,
+                        ']' => depth -= 1,
+                        _ => {}
+                    }
+                    c == '>' && depth == 0
+                });
+                match end {
+                    Some(end) => self.position += end + 1,
+                    None => return Err(self.error("unterminated document type declaration")),
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parses the name of the element whose '<' is at the current position.
+    fn parse_name_after_lt(&mut self) -> Result<&str, InternalError> {
+        self.position += 1;
+        self.parse_name()
+    }
+
+    fn parse_name(&mut self) -> Result<&str, InternalError> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || "/>=".contains(c))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.position += len;
+        Ok(&self.text[self.position - len..self.position])
+    }
+
+    /// Decodes the character and entity references of `raw`, normalising whitespace characters
+    /// to spaces for attribute values.
+    fn decode(&self, raw: &str, offset: usize, attribute: bool) -> Result<String, InternalError> {
+        let mut decoded = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(index) = rest.find(['&', '\r', '\t', '\n']) {
+            decoded.push_str(&rest[..index]);
+            let reference = &rest[index..];
+            if !reference.starts_with('&') {
+                // line breaks are normalised to '\n', and whitespace to spaces in attributes
+                decoded.push(match (attribute, &reference[..1]) {
+                    (true, _) => ' ',
+                    (false, "\t") => '\t',
+                    _ => '\n',
+                });
+                rest = &reference[if reference.starts_with("\r\n") { 2 } else { 1 }..];
+                continue;
+            }
+            let error_offset = offset + raw.len() - reference.len();
+            let invalid = || {
+                InternalError::parse_error_at_offset(self.text, error_offset, "invalid reference")
+            };
+            let end = reference.find(';').ok_or_else(invalid)?;
+            let name = &reference[1..end];
+            let c = match name {
+                "amp" => '&',
+                "lt" => '<',
+                "gt" => '>',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => {
+                    let code = match name.strip_prefix("#x") {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => name.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                    };
+                    code.and_then(char::from_u32).ok_or_else(invalid)?
+                }
+            };
+            decoded.push(c);
+            rest = &reference[end + 1..];
+        }
+        decoded.push_str(rest);
+        Ok(decoded)
+    }
+
+    /// Parses the attributes of a start tag up to its end, returning them with whether the
+    /// element is self-closing.
+    fn parse_attributes(&mut self) -> Result<(HashMap<String, String>, bool), InternalError> {
+        let mut attributes: HashMap<String, String> = HashMap::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.position += 2;
+                return Ok((attributes, true));
+            }
+            if self.rest().starts_with('>') {
+                self.position += 1;
+                return Ok((attributes, false));
+            }
+            let name = self.parse_name()?.to_string();
+            self.skip_whitespace();
+            if !self.rest().starts_with('=') {
+                return Err(self.error("expected '='"));
+            }
+            self.position += 1;
+            self.skip_whitespace();
+            let Some(quote) = self
+                .rest()
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+            else {
+                return Err(self.error("expected a quoted attribute value"));
+            };
+            let start = self.position + 1;
+            let Some(len) = self.text[start..].find(quote) else {
+                return Err(self.error("unterminated attribute value"));
+            };
+            let value = self.decode(&self.text[start..start + len], start, true)?;
+            attributes.insert(name, value);
+            self.position = start + len + 1;
+        }
+    }
+
+    /// Parses the content of an element up to its end tag, returning the decoded text and the
+    /// byte offset where the end tag starts.
+    fn parse_content(&mut self, name: &str) -> Result<(String, usize), InternalError> {
+        let mut content = String::new();
+        loop {
+            let rest = self.rest();
+            let text_len = rest.find('<').unwrap_or(rest.len());
+            content.push_str(&self.decode(&rest[..text_len], self.position, false)?);
+            self.position += text_len;
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Err(self.error(format!("unterminated element '{}'", name).as_str()));
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let Some(len) = cdata.find("]]>") else {
+                    return Err(self.error("unterminated CDATA section"));
+                };
+                content.push_str(&cdata[..len]);
+                self.position += "<![CDATA[".len() + len + "]]>".len();
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->", "unterminated comment")?;
+            } else if let Some(end_tag) = rest.strip_prefix("</") {
+                let content_end = self.position;
+                let end_name = end_tag
+                    .find(|c: char| c.is_whitespace() || c == '>')
+                    .map(|len| &end_tag[..len]);
+                if end_name != Some(name) {
+                    return Err(self.error(format!("expected '</{}>'", name).as_str()));
+                }
+                self.skip_past(">", "unterminated end tag")?;
+                return Ok((content, content_end));
+            } else {
+                return Err(self.error(format!("unexpected element in '{}'", name).as_str()));
+            }
+        }
+    }
+}
+
+/// A properties file in the XML form read by `Properties.loadFromXML`, made of '<entry
+/// key="...">' elements optionally preceded by a '<comment>' element. Only the content of the
+/// updated entries is rewritten, retaining the prolog, the comment and the formatting, while
+/// added properties are inserted after the last entry with its indentation.
+#[derive(Debug)]
+pub struct XmlDocument<'a> {
+    text: &'a str,
+    entries: Vec<XmlEntry>,
+    /// Byte ranges of the entries removed as duplicates
+    removals: Vec<(usize, usize)>,
+    /// Replacement content of the updated entries, by index of the entry
+    replacements: HashMap<usize, String>,
+    insertion: Insertion,
+    /// Whitespace preceding the added entries
+    separator: String,
+    additions: Vec<Property<'a>>,
+}
+
+impl<'a> XmlDocument<'a> {
+    /// Parses `text`, which is considered a document without entries if blank.
+    pub fn parse(text: &'a str) -> Result<XmlDocument<'a>, InternalError> {
+        let mut parser = XmlParser { text, position: 0 };
+        let mut document = XmlDocument {
+            text,
+            entries: Vec::new(),
+            removals: Vec::new(),
+            replacements: HashMap::new(),
+            insertion: Insertion::NewDocument,
+            separator: "\n".to_string(),
+            additions: Vec::new(),
+        };
+        parser.skip_misc()?;
+        if parser.rest().is_empty() {
+            return Ok(document);
+        }
+        let properties_start = parser.position;
+        if !parser.rest().starts_with('<') || parser.parse_name_after_lt()? != "properties" {
+            parser.position = properties_start;
+            return Err(parser.error("expected a '<properties>' element"));
+        }
+        let (_, self_closing) = parser.parse_attributes()?;
+        if self_closing {
+            document.insertion = Insertion::Expand(properties_start, parser.position);
+        } else {
+            document.insertion = Insertion::After(parser.position);
+            document.parse_children(&mut parser)?;
+        }
+        parser.skip_misc()?;
+        if !parser.rest().is_empty() {
+            return Err(parser.error("unexpected content after the '<properties>' element"));
+        }
+        Ok(document)
+    }
+
+    fn parse_children(&mut self, parser: &mut XmlParser) -> Result<(), InternalError> {
+        let mut first_separator: Option<String> = None;
+        loop {
+            let leading = parser.position;
+            parser.skip_whitespace();
+            let whitespace = &self.text[leading..parser.position];
+            let rest = parser.rest();
+            if let Some(end_tag) = rest.strip_prefix("</") {
+                if !end_tag.starts_with("properties") {
+                    return Err(parser.error("expected '</properties>'"));
+                }
+                parser.skip_past(">", "unterminated end tag")?;
+                break;
+            }
+            if rest.starts_with("<!--") {
+                parser.skip_past("-->", "unterminated comment")?;
+            } else if rest.starts_with('<') {
+                let start = parser.position;
+                let name = parser.parse_name_after_lt()?.to_string();
+                let (attributes, self_closing) = parser.parse_attributes()?;
+                let (value, content_start, content_end) = if self_closing {
+                    (String::new(), start, parser.position)
+                } else {
+                    let content_start = parser.position;
+                    let (value, content_end) = parser.parse_content(&name)?;
+                    (value, content_start, content_end)
+                };
+                match name.as_str() {
+                    "comment" => {}
+                    "entry" => {
+                        let Some(key) = attributes.get("key") else {
+                            parser.position = start;
+                            return Err(parser.error("missing 'key' attribute in '<entry>'"));
+                        };
+                        self.entries.push(XmlEntry {
+                            key: key.clone(),
+                            value,
+                            line_num: split_physical_lines(&self.text[..start]).len() as i32,
+                            leading,
+                            content_start,
+                            content_end,
+                            end: parser.position,
+                            self_closing,
+                        });
+                        self.separator = whitespace.to_string();
+                    }
+                    _ => {
+                        parser.position = start;
+                        return Err(parser.error(format!("unexpected element '{}'", name).as_str()));
+                    }
+                }
+            } else if rest.is_empty() {
+                return Err(parser.error("unterminated element 'properties'"));
+            } else {
+                return Err(parser.error("unexpected text in '<properties>'"));
+            }
+            first_separator.get_or_insert_with(|| whitespace.to_string());
+            self.insertion = Insertion::After(parser.position);
+        }
+        if self.entries.is_empty() {
+            self.separator = first_separator
+                .filter(|separator| !separator.is_empty())
+                .unwrap_or("\n".to_string());
+        }
+        Ok(())
+    }
+
+    /// Edits inserting the added properties, as replaced byte ranges of the text and their
+    /// replacement. A blank input is always completed into a document, even without additions.
+    fn addition_edit(&self) -> Option<(usize, usize, String)> {
+        if self.additions.is_empty() && !matches!(self.insertion, Insertion::NewDocument) {
+            return None;
+        }
+        let entries: String = self
+            .additions
+            .iter()
+            .map(|property| {
+                format!(
+                    "{}{}",
+                    self.separator,
+                    entry_element(&property.key, &property.value)
+                )
+            })
+            .collect();
+        Some(match self.insertion {
+            Insertion::After(position) => (position, position, entries),
+            Insertion::Expand(start, end) => (
+                start,
+                end,
+                format!("<properties>{}\n</properties>", entries),
+            ),
+            Insertion::NewDocument => (
+                self.text.len(),
+                self.text.len(),
+                format!("{}<properties>{}\n</properties>\n", PROLOG, entries),
+            ),
+        })
+    }
+}
+
+impl<'a> Document<'a> for XmlDocument<'a> {
+    fn properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                (
+                    index,
+                    Property {
+                        key: Cow::Borrowed(entry.key.as_str()),
+                        value: Cow::Borrowed(entry.value.as_str()),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Applies `policy` to the keys defined more than once, removing the entries which are not
+    /// retained together with the whitespace preceding them.
+    fn resolve_duplicates(&mut self, policy: DuplicatePolicy) -> Result<(), InternalError> {
+        let definitions = self
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| (index, entry.key.as_str(), entry.line_num));
+        let removed = removed_duplicates(definitions, policy)?;
+        let mut index: usize = 0;
+        let removals = &mut self.removals;
+        self.entries.retain(|entry| {
+            let keep = !removed.contains(&index);
+            if !keep {
+                removals.push((entry.leading, entry.end));
+            }
+            index += 1;
+            keep
+        });
+        Ok(())
+    }
+
+    /// Replaces the content of the entry at `index` with the escaped `value`.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError> {
+        let entry = &mut self.entries[index];
+        let replacement = if entry.self_closing {
+            entry_element(&entry.key, value)
+        } else {
+            escape(value, false)
+        };
+        self.replacements.insert(index, replacement);
+        entry.value = value.to_string();
+        Ok(())
+    }
+
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError> {
+        self.additions.push(property);
+        Ok(())
+    }
+
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()> {
+        let mut edits: Vec<(usize, usize, String)> = self
+            .removals
+            .iter()
+            .map(|(start, end)| (*start, *end, String::new()))
+            .collect();
+        for (index, replacement) in &self.replacements {
+            let entry = &self.entries[*index];
+            edits.push((entry.content_start, entry.content_end, replacement.clone()));
+        }
+        edits.extend(self.addition_edit());
+        edits.sort_by_key(|(start, end, _)| (*start, *end));
+        let mut rendered = String::with_capacity(self.text.len());
+        let mut position: usize = 0;
+        for (start, end, replacement) in edits {
+            rendered.push_str(&self.text[position..start]);
+            rendered.push_str(replacement.as_str());
+            position = end;
+        }
+        rendered.push_str(&self.text[position..]);
+        let mut lines = split_physical_lines(&rendered);
+        if lines.last() == Some(&"") {
+            lines.pop();
+        }
+        for line in lines {
+            output.write_all(line.as_bytes())?;
+            output.write_all(line_ending.as_str().as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod xml_document_tests {
+    use super::*;
+
+    fn parse(s: &str) -> XmlDocument<'_> {
+        XmlDocument::parse(s).unwrap()
+    }
+
+    fn properties(document: &XmlDocument) -> Vec<(String, String)> {
+        Document::properties(document)
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect()
+    }
+
+    fn write(document: &XmlDocument) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        document.write(&mut output, LineEnding::Lf).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    const INPUT: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n<!DOCTYPE properties SYSTEM \"http://java.sun.com/dtd/properties.dtd\">\n<properties>\n  <comment>Settings &amp; more</comment>\n  <entry key=\"a\">1</entry>\n  <!-- <entry key=\"b\">2</entry> -->\n  <entry key='c &quot;d&quot;'/>\n  <entry key=\"e\">x &lt; y<![CDATA[ & <z>]]>&#x41;</entry>\n</properties>\n";
+
+    #[test]
+    fn parse_should_decode_entries() {
+        assert_eq!(
+            properties(&parse(INPUT)),
+            pairs(&[("a", "1"), ("c \"d\"", ""), ("e", "x < y & <z>A")])
+        );
+    }
+
+    #[test]
+    fn parse_should_report_errors_with_position() {
+        for (input, line, column, message) in [
+            ("<props/>", 1, 1, "expected a '<properties>' element"),
+            (
+                "<properties>\n<entry>x</entry>",
+                2,
+                1,
+                "missing 'key' attribute in '<entry>'",
+            ),
+            (
+                "<properties>\n<entry key=\"a\">&foo;</entry>",
+                2,
+                16,
+                "invalid reference",
+            ),
+            (
+                "<properties>\n<entry key=\"a\">x</properties>",
+                2,
+                17,
+                "expected '</entry>'",
+            ),
+            (
+                "<properties>\n<other/>\n</properties>",
+                2,
+                1,
+                "unexpected element 'other'",
+            ),
+        ] {
+            match XmlDocument::parse(input).unwrap_err() {
+                InternalError::ParseError {
+                    line_num,
+                    snippet,
+                    message: m,
+                } => assert_eq!(
+                    (line_num, snippet.unwrap().column, m.as_str()),
+                    (line, column, message)
+                ),
+                _ => panic!("error is not ParseError"),
+            }
+        }
+    }
+
+    #[test]
+    fn write_should_reproduce_input_exactly() {
+        assert_eq!(write(&parse(INPUT)), INPUT);
+    }
+
+    #[test]
+    fn set_value_should_escape_values_and_retain_comment() {
+        let mut document = parse(INPUT);
+
+        document.set_value(0, "<a & b>").unwrap();
+        document.set_value(1, "v").unwrap();
+        document.set_value(2, "line\r\n").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n<!DOCTYPE properties SYSTEM \"http://java.sun.com/dtd/properties.dtd\">\n<properties>\n  <comment>Settings &amp; more</comment>\n  <entry key=\"a\">&lt;a &amp; b&gt;</entry>\n  <!-- <entry key=\"b\">2</entry> -->\n  <entry key=\"c &quot;d&quot;\">v</entry>\n  <entry key=\"e\">line&#13;\n</entry>\n</properties>\n"
+        );
+        assert_eq!(
+            properties(&parse(write(&document).as_str()))[2],
+            ("e".to_string(), "line\r\n".to_string())
+        );
+    }
+
+    #[test]
+    fn resolve_duplicates_should_remove_discarded_entries() {
+        let mut document = parse(
+            "<properties>\n  <entry key=\"a\">1</entry>\n  <entry key=\"b\">2</entry>\n  <entry key=\"a\">3</entry>\n</properties>\n",
+        );
+
+        document
+            .resolve_duplicates(DuplicatePolicy::FirstWins)
+            .unwrap();
+
+        assert_eq!(
+            write(&document),
+            "<properties>\n  <entry key=\"a\">1</entry>\n  <entry key=\"b\">2</entry>\n</properties>\n"
+        );
+    }
+
+    #[test]
+    fn push_should_insert_entries_after_last_entry() {
+        let mut document = parse(INPUT);
+
+        document.push(Property::new("f.g", "<1>")).unwrap();
+        document.push(Property::new("h", "2")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            INPUT.replace(
+                "</entry>\n</properties>",
+                "</entry>\n  <entry key=\"f.g\">&lt;1&gt;</entry>\n  <entry key=\"h\">2</entry>\n</properties>"
+            )
+        );
+    }
+
+    #[test]
+    fn declare_encoding_should_replace_or_add_encoding() {
+        assert_eq!(
+            declare_encoding(INPUT, Encoding::Iso88591),
+            INPUT.replace("encoding=\"UTF-8\"", "encoding=\"ISO-8859-1\"")
+        );
+        assert_eq!(
+            declare_encoding("<?xml version='1.0' encoding='latin1' ?>\n", Encoding::Utf8),
+            "<?xml version='1.0' encoding=\"UTF-8\" ?>\n"
+        );
+        assert_eq!(
+            declare_encoding(
+                "<?xml version=\"1.0\"?>\n<properties/>\n",
+                Encoding::Iso88591
+            ),
+            "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\n<properties/>\n"
+        );
+        assert_eq!(
+            declare_encoding("<properties/>\r\n", Encoding::Iso88591),
+            "<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?>\r\n<properties/>\r\n"
+        );
+        assert_eq!(
+            declare_encoding("<properties/>\n", Encoding::Utf8),
+            "<properties/>\n"
+        );
+    }
+
+    #[test]
+    fn write_should_write_properties_element_for_blank_input() {
+        assert_eq!(
+            write(&parse("")),
+            format!("{}<properties>\n</properties>\n", PROLOG)
+        );
+    }
+
+    #[test]
+    fn push_should_create_properties_element_when_missing() {
+        let mut empty = parse("");
+        let mut self_closing = parse("<?xml version=\"1.0\"?>\n<properties/>\n");
+
+        empty.push(Property::new("a", "1")).unwrap();
+        self_closing.push(Property::new("a", "1")).unwrap();
+
+        assert_eq!(
+            write(&empty),
+            format!(
+                "{}<properties>\n<entry key=\"a\">1</entry>\n</properties>\n",
+                PROLOG
+            )
+        );
+        assert_eq!(
+            write(&self_closing),
+            "<?xml version=\"1.0\"?>\n<properties>\n<entry key=\"a\">1</entry>\n</properties>\n"
+        );
+    }
+}
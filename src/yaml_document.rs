@@ -0,0 +1,1526 @@
+use crate::document::Document;
+use crate::keys::{insert_node, key_segments, segments_key, Node, Nodes, Segment};
+use crate::line_ending::split_physical_lines;
+use crate::model::{Format, InternalError, LineEnding, Property};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::Write;
+
+/// Indentation of nested blocks added to documents which contain none.
+const DEFAULT_INDENT_STEP: usize = 2;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ScalarStyle {
+    Plain,
+    SingleQuoted,
+    DoubleQuoted,
+    Block,
+}
+
+/// What a plain scalar is resolved to by YAML 1.1 loaders such as SnakeYAML.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum PlainType {
+    /// A string, or a number written in its canonical decimal form
+    Text,
+    Null,
+    Boolean,
+    /// A number which does not read back as the same text, such as '007', '0x1F' or '1_000', or
+    /// a timestamp
+    Number,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum CollectionKind {
+    Mapping,
+    Sequence,
+}
+
+/// A scalar of the document with the flattened key it is reachable with, such as 'a.b[1].c'.
+#[derive(Debug)]
+struct Scalar {
+    key: String,
+    value: String,
+    /// Index of the line containing the scalar
+    line: usize,
+    /// Byte offset in the line where the scalar starts
+    start: usize,
+    /// Byte offset in the line where the scalar ends
+    end: usize,
+    /// Number of lines following `line` which are part of the scalar, for block scalars
+    continuation_lines: usize,
+    style: ScalarStyle,
+    /// Whether the scalar is an entry of a flow collection
+    in_flow: bool,
+}
+
+/// A block collection of the first document of the stream, where added properties are inserted.
+#[derive(Debug)]
+struct Collection {
+    kind: CollectionKind,
+    /// Indentation of the entries of the collection
+    indent: usize,
+    /// Index of the last line of the collection
+    last_line: usize,
+}
+
+/// A flow collection such as '[a, b]' or '{a: 1}' of the first document of the stream, where
+/// added properties are inserted as entries.
+#[derive(Debug)]
+struct FlowCollection {
+    kind: CollectionKind,
+    /// Index of the line containing the collection
+    line: usize,
+    /// Byte offset in the line of the closing bracket
+    close: usize,
+    len: usize,
+}
+
+impl Segment {
+    fn collection_kind(&self) -> CollectionKind {
+        match self {
+            Segment::Key(_) => CollectionKind::Mapping,
+            Segment::Index(_) => CollectionKind::Sequence,
+        }
+    }
+}
+
+fn child_key(parent: &str, key: &str) -> String {
+    if parent.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", parent, key)
+    }
+}
+
+fn is_sequence_entry(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+fn is_document_marker(content: &str, marker: &str) -> bool {
+    content == marker || content.starts_with(format!("{} ", marker).as_str())
+}
+
+fn parse_double_quoted(text: &str) -> Result<(String, usize), &'static str> {
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, i + 1)),
+            '\\' => {
+                let unescaped = match chars.next().map(|(_, c)| c) {
+                    Some('0') => '\0',
+                    Some('a') => '\x07',
+                    Some('b') => '\x08',
+                    Some('t') | Some('\t') => '\t',
+                    Some('n') => '\n',
+                    Some('v') => '\x0b',
+                    Some('f') => '\x0c',
+                    Some('r') => '\r',
+                    Some('e') => '\x1b',
+                    Some('N') => '\u{85}',
+                    Some('_') => '\u{a0}',
+                    Some('L') => '\u{2028}',
+                    Some('P') => '\u{2029}',
+                    Some(c @ ('x' | 'u' | 'U')) => {
+                        let len = match c {
+                            'x' => 2,
+                            'u' => 4,
+                            _ => 8,
+                        };
+                        let digits: String = chars.by_ref().take(len).map(|(_, c)| c).collect();
+                        u32::from_str_radix(&digits, 16)
+                            .ok()
+                            .filter(|_| digits.len() == len)
+                            .and_then(char::from_u32)
+                            .ok_or("invalid escape sequence")?
+                    }
+                    Some(c @ ('"' | '/' | '\\' | ' ')) => c,
+                    _ => return Err("invalid escape sequence"),
+                };
+                value.push(unescaped);
+            }
+            _ => value.push(c),
+        }
+    }
+    Err("unterminated double-quoted scalar")
+}
+
+fn parse_single_quoted(text: &str) -> Result<(String, usize), &'static str> {
+    let mut value = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != '\'' {
+            value.push(c);
+        } else if let Some((_, '\'')) = chars.peek() {
+            chars.next();
+            value.push('\'');
+        } else {
+            return Ok((value, i + 1));
+        }
+    }
+    Err("unterminated single-quoted scalar")
+}
+
+/// Parses the scalar at the start of `text`, returning its value and its length in `text`.
+fn parse_scalar(text: &str) -> Result<(String, usize, ScalarStyle), &'static str> {
+    match text.chars().next() {
+        Some('"') => {
+            parse_double_quoted(text).map(|(value, len)| (value, len, ScalarStyle::DoubleQuoted))
+        }
+        Some('\'') => {
+            parse_single_quoted(text).map(|(value, len)| (value, len, ScalarStyle::SingleQuoted))
+        }
+        _ => {
+            let end = text.find(" #").unwrap_or(text.len());
+            let len = text[..end].trim_end().len();
+            Ok((text[..len].to_string(), len, ScalarStyle::Plain))
+        }
+    }
+}
+
+/// Splits the key of a mapping entry such as 'key: value', returning the key and the length of
+/// the key including the ':' indicator.
+fn split_key(content: &str) -> Option<(String, usize)> {
+    if content.starts_with(['"', '\'']) {
+        let (key, len, _) = parse_scalar(content).ok()?;
+        let after = content[len..].trim_start_matches(' ').strip_prefix(':')?;
+        return (after.is_empty() || after.starts_with(' '))
+            .then(|| (key, content.len() - after.len()));
+    }
+    if content.starts_with(['[', '{']) {
+        return None;
+    }
+    for (i, c) in content.char_indices() {
+        if c == '#' && content[..i].ends_with(' ') {
+            return None;
+        }
+        let after = &content[i + c.len_utf8()..];
+        if c == ':' && (after.is_empty() || after.starts_with([' ', '\t'])) {
+            let key = content[..i].trim_end();
+            return (!key.is_empty()).then(|| (key.to_string(), i + 1));
+        }
+    }
+    None
+}
+
+fn is_canonical_integer(digits: &str) -> bool {
+    digits == "0"
+        || (digits.starts_with(|c: char| matches!(c, '1'..='9'))
+            && digits.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn plain_type(text: &str) -> PlainType {
+    match text {
+        "~" | "null" | "Null" | "NULL" => return PlainType::Null,
+        "yes" | "Yes" | "YES" | "no" | "No" | "NO" | "true" | "True" | "TRUE" | "false"
+        | "False" | "FALSE" | "on" | "On" | "ON" | "off" | "Off" | "OFF" => {
+            return PlainType::Boolean
+        }
+        _ => {}
+    }
+    let unsigned = text.strip_prefix('-').unwrap_or(text);
+    let canonical = match unsigned.split_once('.') {
+        None => is_canonical_integer(unsigned),
+        Some((integer, fraction)) => {
+            is_canonical_integer(integer)
+                && !fraction.is_empty()
+                && fraction.chars().all(|c| c.is_ascii_digit())
+                && (fraction == "0" || !fraction.ends_with('0'))
+        }
+    };
+    let numeric = text.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c))
+        && text
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() || "+-._:xXoO".contains(c));
+    let special = matches!(
+        text.to_ascii_lowercase().as_str(),
+        ".inf" | "+.inf" | "-.inf" | ".nan"
+    );
+    if !canonical && (numeric || special) {
+        PlainType::Number
+    } else {
+        PlainType::Text
+    }
+}
+
+/// Whether `text` can be written as a plain scalar which reads back as the same string.
+fn is_plain_safe(text: &str) -> bool {
+    let first = match text.chars().next() {
+        None => return false,
+        Some(c) => c,
+    };
+    if "[]{},#&*!|>'\"%@`".contains(first) {
+        return false;
+    }
+    if "-?:".contains(first) && text[1..].chars().next().is_none_or(|c| c == ' ') {
+        return false;
+    }
+    !text.starts_with(char::is_whitespace)
+        && !text.ends_with(char::is_whitespace)
+        && !text.ends_with(':')
+        && !text.contains(": ")
+        && !text.contains(" #")
+        && !text.chars().any(char::is_control)
+        && plain_type(text) == PlainType::Text
+}
+
+fn double_quote(text: &str) -> String {
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_control() && (c as u32) <= 0xFF => {
+                result.push_str(format!("\\x{:02X}", c as u32).as_str())
+            }
+            c if c.is_control() => result.push_str(format!("\\u{:04X}", c as u32).as_str()),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Renders `value` as a scalar, keeping `style` when it can represent the value.
+fn render_value(value: &str, style: ScalarStyle) -> String {
+    match style {
+        ScalarStyle::SingleQuoted if !value.chars().any(char::is_control) => {
+            format!("'{}'", value.replace('\'', "''"))
+        }
+        ScalarStyle::Plain | ScalarStyle::Block if is_plain_safe(value) => value.to_string(),
+        _ => double_quote(value),
+    }
+}
+
+fn render_key(key: &str) -> String {
+    if is_plain_safe(key) {
+        key.to_string()
+    } else {
+        double_quote(key)
+    }
+}
+
+fn has_flow_indicator(text: &str) -> bool {
+    text.contains([',', '[', ']', '{', '}'])
+}
+
+/// Renders `value` as a scalar of a flow collection, where plain scalars cannot contain flow
+/// indicators.
+fn render_flow_value(value: &str, style: ScalarStyle) -> String {
+    if style == ScalarStyle::Plain && has_flow_indicator(value) {
+        double_quote(value)
+    } else {
+        render_value(value, style)
+    }
+}
+
+/// Renders the entries of a flow collection, nesting children into flow collections.
+fn render_flow_entries(children: &[(Segment, Node)]) -> Vec<String> {
+    children
+        .iter()
+        .map(|(segment, node)| {
+            let value = match node {
+                Node::Value(value) => render_flow_value(value, ScalarStyle::Plain),
+                Node::Children(grandchildren) => {
+                    let entries = render_flow_entries(grandchildren).join(", ");
+                    match grandchildren.first() {
+                        Some((Segment::Index(_), _)) => format!("[{}]", entries),
+                        _ => format!("{{{}}}", entries),
+                    }
+                }
+            };
+            match segment {
+                Segment::Key(key) if has_flow_indicator(key) => {
+                    format!("{}: {}", double_quote(key), value)
+                }
+                Segment::Key(key) => format!("{}: {}", render_key(key), value),
+                Segment::Index(_) => value,
+            }
+        })
+        .collect()
+}
+
+fn render_nodes(children: &[(Segment, Node)], indent: usize, step: usize, lines: &mut Vec<String>) {
+    let padding = " ".repeat(indent);
+    for (segment, node) in children {
+        match (segment, node) {
+            (Segment::Key(key), Node::Value(value)) => lines.push(format!(
+                "{}{}: {}",
+                padding,
+                render_key(key),
+                render_value(value, ScalarStyle::Plain)
+            )),
+            (Segment::Key(key), Node::Children(grandchildren)) => {
+                lines.push(format!("{}{}:", padding, render_key(key)));
+                render_nodes(grandchildren, indent + step, step, lines);
+            }
+            (Segment::Index(_), Node::Value(value)) => lines.push(format!(
+                "{}- {}",
+                padding,
+                render_value(value, ScalarStyle::Plain)
+            )),
+            (Segment::Index(_), Node::Children(grandchildren)) => {
+                // render the entries of the item as a block and start its first line with the
+                // sequence indicator
+                let first = lines.len();
+                render_nodes(grandchildren, indent + 2, step, lines);
+                if let Some(line) = lines.get_mut(first) {
+                    line.replace_range(indent..indent + 2, "- ");
+                }
+            }
+        }
+    }
+}
+
+/// A block collection being parsed.
+struct Frame {
+    key: String,
+    indent: usize,
+    kind: CollectionKind,
+    len: usize,
+}
+
+/// A key without a value on its line, whose value is either the nested block starting on the
+/// next line or null.
+struct PendingKey {
+    key: String,
+    indent: usize,
+    line: usize,
+    end: usize,
+}
+
+#[derive(Default)]
+struct YamlParser {
+    frames: Vec<Frame>,
+    pending: Option<PendingKey>,
+    document: usize,
+    document_started: bool,
+    scalars: Vec<Scalar>,
+    collections: HashMap<String, Collection>,
+    flows: HashMap<String, FlowCollection>,
+    indent_step: Option<usize>,
+}
+
+fn skip_blanks(line: &str, position: usize) -> usize {
+    line.len() - line[position..].trim_start_matches([' ', '\t']).len()
+}
+
+fn error(lines: &[&str], index: usize, column: usize, message: &str) -> InternalError {
+    let line = lines[index];
+    InternalError::parse_error_at(
+        index as i32 + 1,
+        line[..column].chars().count() as i32 + 1,
+        line,
+        message,
+    )
+}
+
+impl YamlParser {
+    /// Parses the line at `index`, returning the number of lines consumed.
+    fn parse_line(&mut self, lines: &[&str], index: usize) -> Result<usize, InternalError> {
+        let line = lines[index];
+        let content = line.trim_start_matches(' ');
+        let indent = line.len() - content.len();
+        if content.trim().is_empty() || content.starts_with('#') {
+            return Ok(1);
+        }
+        if indent == 0 {
+            if is_document_marker(content, "---") || is_document_marker(content, "...") {
+                self.end_document();
+                return Ok(1);
+            }
+            if content.starts_with('%') {
+                return Ok(1);
+            }
+        }
+        self.resolve_pending(indent, content);
+        while let Some(frame) = self.frames.last() {
+            let closed = frame.indent > indent
+                || (frame.indent == indent
+                    && frame.kind == CollectionKind::Sequence
+                    && !is_sequence_entry(content)
+                    && self.frames.len() > 1);
+            if !closed {
+                break;
+            }
+            self.frames.pop();
+        }
+        if self.frames.is_empty() {
+            let kind = if is_sequence_entry(content) {
+                CollectionKind::Sequence
+            } else {
+                CollectionKind::Mapping
+            };
+            self.push_frame(String::new(), indent, kind, index);
+        }
+        if self.frames.last().unwrap().indent != indent {
+            return Err(error(lines, index, indent, "unexpected indentation"));
+        }
+        self.document_started = true;
+        let consumed = self.parse_node(lines, index, indent, content)?;
+        if self.document == 0 {
+            for frame in &self.frames {
+                if let Some(collection) = self.collections.get_mut(&frame.key) {
+                    collection.last_line = index + consumed - 1;
+                }
+            }
+        }
+        Ok(consumed)
+    }
+
+    fn parse_node(
+        &mut self,
+        lines: &[&str],
+        index: usize,
+        column: usize,
+        content: &str,
+    ) -> Result<usize, InternalError> {
+        let (mut column, mut content) = (column, content);
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            match frame.kind {
+                CollectionKind::Sequence => {
+                    if !is_sequence_entry(content) {
+                        return Err(error(lines, index, column, "expected a sequence entry"));
+                    }
+                    let key = format!("{}[{}]", frame.key, frame.len);
+                    frame.len += 1;
+                    let rest = content[1..].trim_start_matches(' ');
+                    let rest_column = column + content.len() - rest.len();
+                    if rest.is_empty() || rest.starts_with('#') {
+                        self.pending = Some(PendingKey {
+                            key,
+                            indent: column,
+                            line: index,
+                            end: column + 1,
+                        });
+                        return Ok(1);
+                    }
+                    if is_sequence_entry(rest) {
+                        self.push_frame(key, rest_column, CollectionKind::Sequence, index);
+                    } else if split_key(rest).is_some() {
+                        self.push_frame(key, rest_column, CollectionKind::Mapping, index);
+                    } else {
+                        return self.parse_value(lines, index, key, column, rest_column, rest);
+                    }
+                    (column, content) = (rest_column, rest);
+                }
+                CollectionKind::Mapping => {
+                    let (key, key_len) = split_key(content)
+                        .ok_or_else(|| error(lines, index, column, "expected a mapping entry"))?;
+                    let key = child_key(&frame.key, &key);
+                    let rest = content[key_len..].trim_start_matches(' ');
+                    let rest_column = column + content.len() - rest.len();
+                    if rest.is_empty() || rest.starts_with('#') {
+                        self.pending = Some(PendingKey {
+                            key,
+                            indent: column,
+                            line: index,
+                            end: column + key_len,
+                        });
+                        return Ok(1);
+                    }
+                    return self.parse_value(lines, index, key, column, rest_column, rest);
+                }
+            }
+        }
+    }
+
+    fn parse_value(
+        &mut self,
+        lines: &[&str],
+        index: usize,
+        key: String,
+        indent: usize,
+        column: usize,
+        text: &str,
+    ) -> Result<usize, InternalError> {
+        if text.starts_with(['|', '>']) {
+            return Ok(self.parse_block_scalar(lines, index, key, indent, column, text));
+        }
+        if text.starts_with(['&', '*']) {
+            return Err(error(
+                lines,
+                index,
+                column,
+                "anchors and aliases are not supported",
+            ));
+        }
+        if text.starts_with('!') {
+            return Err(error(lines, index, column, "tags are not supported"));
+        }
+        let len = if text.starts_with(['[', '{']) {
+            self.parse_flow(lines, index, key, column)? - column
+        } else {
+            let (value, len, style) =
+                parse_scalar(text).map_err(|message| error(lines, index, column, message))?;
+            self.scalars.push(Scalar {
+                key,
+                value,
+                line: index,
+                start: column,
+                end: column + len,
+                continuation_lines: 0,
+                style,
+                in_flow: false,
+            });
+            len
+        };
+        let after = text[len..].trim_start_matches(' ');
+        if !after.is_empty() && !after.starts_with('#') {
+            return Err(error(
+                lines,
+                index,
+                column + text.len() - after.len(),
+                "unexpected characters after value",
+            ));
+        }
+        Ok(1)
+    }
+
+    /// Parses the flow collection starting at `column` of the line at `index`, flattening its
+    /// entries into scalars, and returns the offset following its closing bracket. Flow
+    /// collections spanning several lines are not supported.
+    fn parse_flow(
+        &mut self,
+        lines: &[&str],
+        index: usize,
+        key: String,
+        column: usize,
+    ) -> Result<usize, InternalError> {
+        let line = lines[index];
+        let (kind, closing) = if line[column..].starts_with('[') {
+            (CollectionKind::Sequence, ']')
+        } else {
+            (CollectionKind::Mapping, '}')
+        };
+        let mut len: usize = 0;
+        let mut position = skip_blanks(line, column + 1);
+        while !line[position..].starts_with(closing) {
+            if position == line.len() {
+                return Err(error(lines, index, column, "unterminated flow collection"));
+            }
+            position = match kind {
+                CollectionKind::Sequence => {
+                    let entry_key = format!("{}[{}]", key, len);
+                    self.parse_flow_node(lines, index, entry_key, position)?
+                }
+                CollectionKind::Mapping => {
+                    let (entry_key, after_key) = self.parse_flow_key(lines, index, position)?;
+                    let entry_key = child_key(&key, &entry_key);
+                    self.parse_flow_node(lines, index, entry_key, after_key)?
+                }
+            };
+            len += 1;
+            position = skip_blanks(line, position);
+            if line[position..].starts_with(',') {
+                position = skip_blanks(line, position + 1);
+            } else if position == line.len() {
+                return Err(error(lines, index, column, "unterminated flow collection"));
+            } else if !line[position..].starts_with(closing) {
+                return Err(error(
+                    lines,
+                    index,
+                    position,
+                    "expected ',' in flow collection",
+                ));
+            }
+        }
+        if self.document == 0 {
+            self.flows.insert(
+                key,
+                FlowCollection {
+                    kind,
+                    line: index,
+                    close: position,
+                    len,
+                },
+            );
+        }
+        Ok(position + 1)
+    }
+
+    /// Parses the key of a flow mapping entry at `position`, returning it with the offset
+    /// following its ':' indicator.
+    fn parse_flow_key(
+        &self,
+        lines: &[&str],
+        index: usize,
+        position: usize,
+    ) -> Result<(String, usize), InternalError> {
+        let line = lines[index];
+        let text = &line[position..];
+        let (key, len) = if text.starts_with(['"', '\'']) {
+            let (key, len, _) =
+                parse_scalar(text).map_err(|message| error(lines, index, position, message))?;
+            (key, len)
+        } else {
+            let len = text
+                .char_indices()
+                .find(|(i, c)| {
+                    ",[]{}".contains(*c)
+                        || (*c == ':'
+                            && text[i + 1..]
+                                .chars()
+                                .next()
+                                .is_none_or(|next| " \t,[]{}".contains(next)))
+                })
+                .map(|(i, _)| i)
+                .unwrap_or(text.len());
+            (text[..len].trim_end().to_string(), len)
+        };
+        let colon = skip_blanks(line, position + len);
+        if key.is_empty() || !line[colon..].starts_with(':') {
+            return Err(error(lines, index, position, "expected a mapping entry"));
+        }
+        Ok((key, colon + 1))
+    }
+
+    /// Parses the value of a flow collection entry following `position`, returning the offset
+    /// following it.
+    fn parse_flow_node(
+        &mut self,
+        lines: &[&str],
+        index: usize,
+        key: String,
+        position: usize,
+    ) -> Result<usize, InternalError> {
+        let line = lines[index];
+        let start = skip_blanks(line, position);
+        let text = &line[start..];
+        if text.starts_with(['[', '{']) {
+            return self.parse_flow(lines, index, key, start);
+        }
+        if text.starts_with(['&', '*']) {
+            return Err(error(
+                lines,
+                index,
+                start,
+                "anchors and aliases are not supported",
+            ));
+        }
+        if text.starts_with('!') {
+            return Err(error(lines, index, start, "tags are not supported"));
+        }
+        let (value, start, end, style) = if text.is_empty() || text.starts_with([',', ']', '}']) {
+            // null values have no text, like those of block mappings
+            (String::new(), position, position, ScalarStyle::Plain)
+        } else if text.starts_with(['"', '\'']) {
+            let (value, len, style) =
+                parse_scalar(text).map_err(|message| error(lines, index, start, message))?;
+            (value, start, start + len, style)
+        } else {
+            let len = text
+                .find([',', '[', ']', '{', '}'])
+                .unwrap_or(text.len())
+                .min(text.find(" #").unwrap_or(text.len()));
+            let value = text[..len].trim_end();
+            (
+                value.to_string(),
+                start,
+                start + value.len(),
+                ScalarStyle::Plain,
+            )
+        };
+        self.scalars.push(Scalar {
+            key,
+            value,
+            line: index,
+            start,
+            end,
+            continuation_lines: 0,
+            style,
+            in_flow: true,
+        });
+        Ok(end)
+    }
+
+    /// Parses a literal ('|') or folded ('>') block scalar, whose content is made of the
+    /// following lines indented more than its key.
+    fn parse_block_scalar(
+        &mut self,
+        lines: &[&str],
+        index: usize,
+        key: String,
+        indent: usize,
+        column: usize,
+        text: &str,
+    ) -> usize {
+        let header = text[..text.find(" #").unwrap_or(text.len())].trim_end();
+        let mut block_lines: Vec<&str> = Vec::new();
+        let mut content_indent: Option<usize> = None;
+        let mut end = index + 1;
+        while end < lines.len() {
+            let line = lines[end];
+            let content = line.trim_start_matches(' ');
+            let line_indent = line.len() - content.len();
+            if content.is_empty() {
+                block_lines.push("");
+            } else if line_indent <= indent
+                || line_indent < *content_indent.get_or_insert(line_indent)
+            {
+                break;
+            } else {
+                block_lines.push(&line[content_indent.unwrap()..]);
+            }
+            end += 1;
+        }
+        // trailing blank lines are left to the document and only contribute to the value
+        let content_len = block_lines.len()
+            - block_lines
+                .iter()
+                .rev()
+                .take_while(|l| l.is_empty())
+                .count();
+        let trailing_newlines = block_lines.len() - content_len;
+        block_lines.truncate(content_len);
+        let mut value = if header.starts_with('|') {
+            block_lines.join("\n")
+        } else {
+            let mut folded = String::new();
+            let mut preceding_blank = true;
+            for line in &block_lines {
+                if line.is_empty() {
+                    folded.push('\n');
+                } else {
+                    if !preceding_blank {
+                        folded.push(' ');
+                    }
+                    folded.push_str(line);
+                }
+                preceding_blank = line.is_empty();
+            }
+            folded
+        };
+        if !block_lines.is_empty() && !header.contains('-') {
+            let newlines = if header.contains('+') {
+                trailing_newlines + 1
+            } else {
+                1
+            };
+            value.push_str("\n".repeat(newlines).as_str());
+        }
+        self.scalars.push(Scalar {
+            key,
+            value,
+            line: index,
+            start: column,
+            end: column + header.len(),
+            continuation_lines: content_len,
+            style: ScalarStyle::Block,
+            in_flow: false,
+        });
+        content_len + 1
+    }
+
+    fn push_frame(&mut self, key: String, indent: usize, kind: CollectionKind, line: usize) {
+        if self.document == 0 {
+            self.collections.entry(key.clone()).or_insert(Collection {
+                kind,
+                indent,
+                last_line: line,
+            });
+        }
+        self.frames.push(Frame {
+            key,
+            indent,
+            kind,
+            len: 0,
+        });
+    }
+
+    /// Opens the block of the pending key if the line at `indent` is nested in it, otherwise
+    /// records the key with a null value.
+    fn resolve_pending(&mut self, indent: usize, content: &str) {
+        if let Some(pending) = self.pending.take() {
+            let sequence = is_sequence_entry(content);
+            if indent > pending.indent || (indent == pending.indent && sequence) {
+                let kind = if sequence {
+                    CollectionKind::Sequence
+                } else {
+                    self.indent_step.get_or_insert(indent - pending.indent);
+                    CollectionKind::Mapping
+                };
+                self.push_frame(pending.key, indent, kind, pending.line);
+            } else {
+                self.push_null(pending);
+            }
+        }
+    }
+
+    fn push_null(&mut self, pending: PendingKey) {
+        self.scalars.push(Scalar {
+            key: pending.key,
+            value: String::new(),
+            line: pending.line,
+            start: pending.end,
+            end: pending.end,
+            continuation_lines: 0,
+            style: ScalarStyle::Plain,
+            in_flow: false,
+        });
+    }
+
+    fn end_document(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.push_null(pending);
+        }
+        self.frames.clear();
+        if self.document_started {
+            self.document += 1;
+            self.document_started = false;
+        }
+    }
+}
+
+/// A YAML stream whose scalars are addressed by flattened keys, as Spring Boot does: nested
+/// mapping keys are joined with '.' and sequence items are addressed as '[n]', including those of
+/// flow collections. Only the text of the updated scalars is rewritten, so comments, ordering and
+/// formatting are retained. Added properties are inserted as nested blocks into the deepest
+/// existing block collection of the first document, or as entries of its flow collections.
+#[derive(Debug)]
+pub struct YamlDocument<'a> {
+    /// Lines of the document, `None` for the lines of replaced block scalars
+    lines: Vec<Option<Cow<'a, str>>>,
+    scalars: Vec<Scalar>,
+    /// Block collections of the first document by flattened key, the root having an empty key
+    collections: HashMap<String, Collection>,
+    /// Flow collections of the first document by flattened key
+    flows: HashMap<String, FlowCollection>,
+    indent_step: usize,
+    additions: Vec<Property<'a>>,
+}
+
+/// Entries added to a flow collection, inserted before its closing bracket.
+struct FlowEntries {
+    line: usize,
+    position: usize,
+    text: String,
+}
+
+impl<'a> YamlDocument<'a> {
+    /// Parses the YAML constructs used in configuration files: block mappings and sequences, flow
+    /// collections on a single line, plain, quoted and block scalars, comments and multiple
+    /// documents. Anchors and aliases are not supported.
+    pub fn parse(text: &'a str) -> Result<YamlDocument<'a>, InternalError> {
+        let mut lines = split_physical_lines(text);
+        if lines.last() == Some(&"") {
+            lines.pop();
+        }
+        let mut parser = YamlParser::default();
+        let mut index: usize = 0;
+        while index < lines.len() {
+            index += parser.parse_line(&lines, index)?;
+        }
+        parser.end_document();
+        Ok(YamlDocument {
+            lines: lines.into_iter().map(|l| Some(Cow::Borrowed(l))).collect(),
+            scalars: parser.scalars,
+            collections: parser.collections,
+            flows: parser.flows,
+            indent_step: parser.indent_step.unwrap_or(DEFAULT_INDENT_STEP),
+            additions: Vec::new(),
+        })
+    }
+
+    /// Renders the added properties, returning the lines to insert after each line of the
+    /// document, the lines to append at its end and the entries to insert into flow collections.
+    fn render_additions(&self) -> (HashMap<usize, Vec<String>>, Vec<String>, Vec<FlowEntries>) {
+        // flow collections cannot be expanded into blocks, like scalars
+        let scalar_keys: HashSet<&str> = self
+            .scalars
+            .iter()
+            .map(|s| s.key.as_str())
+            .chain(self.flows.keys().map(String::as_str))
+            .collect();
+        let mut trees: Vec<(Option<&Collection>, Nodes)> = Vec::new();
+        let mut flow_trees: Vec<(&FlowCollection, Nodes)> = Vec::new();
+        for property in &self.additions {
+            let segments = key_segments(&property.key);
+            let flow = (1..segments.len()).rev().find_map(|n| {
+                self.flows
+                    .get(&segments_key(&segments[..n]))
+                    .filter(|f| f.kind == segments[n].collection_kind())
+                    .map(|f| (n, f))
+            });
+            if let Some((n, flow)) = flow {
+                let remaining = if (n + 1..segments.len())
+                    .any(|k| scalar_keys.contains(segments_key(&segments[..k]).as_str()))
+                {
+                    vec![Segment::Key(segments_key(&segments[n..]))]
+                } else {
+                    segments[n..].to_vec()
+                };
+                let index = flow_trees
+                    .iter()
+                    .position(|(f, _)| std::ptr::eq(*f, flow))
+                    .unwrap_or_else(|| {
+                        flow_trees.push((flow, Vec::new()));
+                        flow_trees.len() - 1
+                    });
+                insert_node(&mut flow_trees[index].1, &remaining, &property.value);
+                continue;
+            }
+            let attach = (0..segments.len()).rev().find_map(|n| {
+                self.collections
+                    .get(&segments_key(&segments[..n]))
+                    .filter(|c| c.kind == segments[n].collection_kind())
+                    .map(|c| (n, c))
+            });
+            let (n, collection) = match attach {
+                Some((n, collection)) => (n, Some(collection)),
+                None => (0, None),
+            };
+            // keys nested under an existing scalar cannot be expanded into blocks
+            let remaining = if (n + 1..segments.len())
+                .any(|k| scalar_keys.contains(segments_key(&segments[..k]).as_str()))
+            {
+                vec![Segment::Key(segments_key(&segments[n..]))]
+            } else {
+                segments[n..].to_vec()
+            };
+            let position = trees.iter().position(|(c, _)| match (c, collection) {
+                (Some(c), Some(collection)) => std::ptr::eq(*c, collection),
+                (None, None) => true,
+                _ => false,
+            });
+            let index = position.unwrap_or_else(|| {
+                trees.push((collection, Vec::new()));
+                trees.len() - 1
+            });
+            insert_node(&mut trees[index].1, &remaining, &property.value);
+        }
+        // blocks added after the same line are nested in each other: the deepest comes first
+        trees.sort_by_key(|(collection, _)| Reverse(collection.map(|c| c.indent)));
+        let mut after: HashMap<usize, Vec<String>> = HashMap::new();
+        let mut end: Vec<String> = Vec::new();
+        for (collection, children) in trees {
+            match collection {
+                Some(collection) => render_nodes(
+                    &children,
+                    collection.indent,
+                    self.indent_step,
+                    after.entry(collection.last_line).or_default(),
+                ),
+                None => render_nodes(&children, 0, self.indent_step, &mut end),
+            }
+        }
+        let flow_entries = flow_trees
+            .into_iter()
+            .map(|(flow, children)| {
+                let entries = render_flow_entries(&children).join(", ");
+                FlowEntries {
+                    line: flow.line,
+                    position: flow.close,
+                    text: if flow.len == 0 {
+                        entries
+                    } else {
+                        format!(", {}", entries)
+                    },
+                }
+            })
+            .collect();
+        (after, end, flow_entries)
+    }
+}
+
+impl<'a> Document<'a> for YamlDocument<'a> {
+    fn properties(&self) -> Vec<(usize, Property<'_>)> {
+        self.scalars
+            .iter()
+            .enumerate()
+            .map(|(index, scalar)| {
+                (
+                    index,
+                    Property {
+                        key: Cow::Borrowed(scalar.key.as_str()),
+                        value: Cow::Borrowed(scalar.value.as_str()),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    /// Replaces the text of the scalar at `index`, retaining its quoting style when possible.
+    fn set_value(&mut self, index: usize, value: &str) -> Result<(), InternalError> {
+        let scalar = &mut self.scalars[index];
+        let (line, original_end) = (scalar.line, scalar.end);
+        // plain values such as booleans are replaced by plain values of the same type
+        let retains_type = scalar.style == ScalarStyle::Plain
+            && plain_type(&scalar.value) != PlainType::Text
+            && plain_type(&scalar.value) == plain_type(value)
+            && !has_flow_indicator(value);
+        let rendered = if retains_type {
+            value.to_string()
+        } else if scalar.in_flow {
+            render_flow_value(value, scalar.style)
+        } else {
+            render_value(value, scalar.style)
+        };
+        let text = self.lines[scalar.line].as_mut().unwrap().to_mut();
+        if scalar.start == scalar.end {
+            // null values have no text, separate the new value from its indicator
+            text.insert(scalar.start, ' ');
+            scalar.start += 1;
+            scalar.end += 1;
+        }
+        text.replace_range(scalar.start..scalar.end, rendered.as_str());
+        scalar.end = scalar.start + rendered.len();
+        for line in &mut self.lines[scalar.line + 1..=scalar.line + scalar.continuation_lines] {
+            *line = None;
+        }
+        scalar.continuation_lines = 0;
+        scalar.value = value.to_string();
+        // the following entries of a flow collection are moved by the new text
+        let end = scalar.end;
+        for (other, scalar) in self.scalars.iter_mut().enumerate() {
+            if other != index && scalar.line == line && scalar.start >= original_end {
+                scalar.start = scalar.start + end - original_end;
+                scalar.end = scalar.end + end - original_end;
+            }
+        }
+        for flow in self.flows.values_mut() {
+            if flow.line == line && flow.close >= original_end {
+                flow.close = flow.close + end - original_end;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the property to the first document, failing when its root is a collection of
+    /// another kind than the first segment of the key, such as a sequence for 'a.b'.
+    fn push(&mut self, property: Property<'a>) -> Result<(), InternalError> {
+        let root = self.collections.get("").map(|collection| collection.kind);
+        let kind = key_segments(&property.key)
+            .first()
+            .map(Segment::collection_kind);
+        if let (Some(root), Some(kind)) = (root, kind) {
+            if root != kind {
+                let reason = match root {
+                    CollectionKind::Mapping => "the root of the document is a mapping",
+                    CollectionKind::Sequence => "the root of the document is a sequence",
+                };
+                return Err(InternalError::unrepresentable_property(
+                    property.key.as_ref(),
+                    Format::Yaml,
+                    reason,
+                ));
+            }
+        }
+        self.additions.push(property);
+        Ok(())
+    }
+
+    fn write(&self, output: &mut dyn Write, line_ending: LineEnding) -> io::Result<()> {
+        let (after, end, flow_entries) = self.render_additions();
+        let mut write_line = |line: &str| -> io::Result<()> {
+            output.write_all(line.as_bytes())?;
+            output.write_all(line_ending.as_str().as_bytes())
+        };
+        for (index, line) in self.lines.iter().enumerate() {
+            if let Some(line) = line {
+                let mut entries: Vec<&FlowEntries> =
+                    flow_entries.iter().filter(|e| e.line == index).collect();
+                if entries.is_empty() {
+                    write_line(line)?;
+                } else {
+                    // insert from the end of the line so that positions remain valid
+                    entries.sort_by_key(|e| Reverse(e.position));
+                    let mut text = line.to_string();
+                    for entry in entries {
+                        text.insert_str(entry.position, &entry.text);
+                    }
+                    write_line(&text)?;
+                }
+            }
+            for added in after.get(&index).into_iter().flatten() {
+                write_line(added)?;
+            }
+        }
+        for added in &end {
+            write_line(added)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod yaml_document_tests {
+    use super::*;
+
+    fn parse(s: &str) -> YamlDocument<'_> {
+        YamlDocument::parse(s).unwrap()
+    }
+
+    fn properties(document: &YamlDocument) -> Vec<(String, String)> {
+        document
+            .properties()
+            .into_iter()
+            .map(|(_, p)| (p.key.to_string(), p.value.to_string()))
+            .collect()
+    }
+
+    fn write(document: &YamlDocument) -> String {
+        let mut output: Vec<u8> = Vec::new();
+        document.write(&mut output, LineEnding::Lf).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn parse_error_message(s: &str) -> String {
+        YamlDocument::parse(s).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parse_should_flatten_nested_mappings_and_sequences() {
+        let document = parse(
+            "server:\n  port: 8080\n  ssl:\n    enabled: true\nhosts:\n  - a\n  - b\nusers:\n- name: x\n  roles:\n    - admin\n- name: y\n",
+        );
+
+        assert_eq!(
+            properties(&document),
+            pairs(&[
+                ("server.port", "8080"),
+                ("server.ssl.enabled", "true"),
+                ("hosts[0]", "a"),
+                ("hosts[1]", "b"),
+                ("users[0].name", "x"),
+                ("users[0].roles[0]", "admin"),
+                ("users[1].name", "y"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_should_decode_quoted_scalars_and_ignore_comments() {
+        let document = parse(
+            "# comment\na: \"x\\ty\\u00e0\" # trailing\nb: 'it''s'\n\"c.d\": plain value # c\ne: [1, 2]\nf: http://host:80/#anchor\n",
+        );
+
+        assert_eq!(
+            properties(&document),
+            pairs(&[
+                ("a", "x\ty\u{e0}"),
+                ("b", "it's"),
+                ("c.d", "plain value"),
+                ("e[0]", "1"),
+                ("e[1]", "2"),
+                ("f", "http://host:80/#anchor"),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_should_read_block_scalars() {
+        let document = parse("a: |\n  one\n  two\n\nb: >-\n  three\n  four\n\n  five\nc: 1\n");
+
+        assert_eq!(
+            properties(&document),
+            pairs(&[("a", "one\ntwo\n"), ("b", "three four\nfive"), ("c", "1")])
+        );
+    }
+
+    #[test]
+    fn parse_should_read_null_values() {
+        let document = parse("a:\nb: # comment\nc:\n  - \n");
+
+        assert_eq!(
+            properties(&document),
+            pairs(&[("a", ""), ("b", ""), ("c[0]", "")])
+        );
+    }
+
+    #[test]
+    fn parse_should_read_all_documents() {
+        let document = parse("a: 1\n---\na: 2\n");
+
+        assert_eq!(properties(&document), pairs(&[("a", "1"), ("a", "2")]));
+    }
+
+    #[test]
+    fn parse_should_report_errors_with_position() {
+        assert_eq!(
+            parse_error_message("a: 1\n  b: 2\n"),
+            "cannot parse property at line 2, column 3: unexpected indentation\n  b: 2\n  ^"
+        );
+        assert_eq!(
+            parse_error_message("a:\n  b: \"x\n"),
+            "cannot parse property at line 2, column 6: unterminated double-quoted scalar\n  b: \"x\n     ^"
+        );
+        assert_eq!(
+            parse_error_message("a: 1\nplain text\n"),
+            "cannot parse property at line 2, column 1: expected a mapping entry\nplain text\n^"
+        );
+    }
+
+    #[test]
+    fn parse_should_flatten_flow_collections() {
+        let document =
+            parse("list: [1, \"a,b\", {x: 1}]\nmap: {a: 1, 'b': [x, y], c:} # c\nempty: []\n");
+
+        assert_eq!(
+            properties(&document),
+            pairs(&[
+                ("list[0]", "1"),
+                ("list[1]", "a,b"),
+                ("list[2].x", "1"),
+                ("map.a", "1"),
+                ("map.b[0]", "x"),
+                ("map.b[1]", "y"),
+                ("map.c", ""),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_should_reject_tags() {
+        assert_eq!(
+            parse_error_message("a: !!str 123\n"),
+            "cannot parse property at line 1, column 4: tags are not supported\na: !!str 123\n   ^"
+        );
+        assert_eq!(
+            parse_error_message("a: [!!int 1]\n"),
+            "cannot parse property at line 1, column 5: tags are not supported\na: [!!int 1]\n    ^"
+        );
+    }
+
+    #[test]
+    fn parse_should_reject_anchors_and_aliases() {
+        assert_eq!(
+            parse_error_message("a: &x 1\nb: *x\n"),
+            "cannot parse property at line 1, column 4: anchors and aliases are not supported\na: &x 1\n   ^"
+        );
+        assert_eq!(
+            parse_error_message("a:\n  - [*x]\n"),
+            "cannot parse property at line 2, column 6: anchors and aliases are not supported\n  - [*x]\n     ^"
+        );
+    }
+
+    #[test]
+    fn parse_should_report_unterminated_flow_collections() {
+        assert_eq!(
+            parse_error_message("a: [1, 2\n"),
+            "cannot parse property at line 1, column 4: unterminated flow collection\na: [1, 2\n   ^"
+        );
+    }
+
+    #[test]
+    fn write_should_reproduce_input_exactly() {
+        let input = "# header\nserver:\n    port: 8080 # port\n\n    hosts: [a, b]\nlist:\n- 'x'\n- |\n  text\n---\nother: \"y\"\n";
+        let document = parse(input);
+
+        assert_eq!(write(&document), input);
+    }
+
+    #[test]
+    fn write_should_use_given_line_ending() {
+        let document = parse("a:\r\n  b: 1\r\n");
+        let mut output: Vec<u8> = Vec::new();
+
+        document.write(&mut output, LineEnding::CrLf).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "a:\r\n  b: 1\r\n");
+    }
+
+    #[test]
+    fn set_value_should_replace_only_the_scalar() {
+        let mut document = parse("a:\n  b: 1 # comment\n  c: 'x'\n  d: \"y\"\nl:\n  - z\n");
+
+        document.set_value(0, "2").unwrap();
+        document.set_value(1, "it's").unwrap();
+        document.set_value(2, "new\nline").unwrap();
+        document.set_value(3, "w").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "a:\n  b: 2 # comment\n  c: 'it''s'\n  d: \"new\\nline\"\nl:\n  - w\n"
+        );
+        assert_eq!(
+            properties(&document),
+            pairs(&[
+                ("a.b", "2"),
+                ("a.c", "it's"),
+                ("a.d", "new\nline"),
+                ("l[0]", "w")
+            ])
+        );
+    }
+
+    #[test]
+    fn set_value_should_quote_values_which_are_not_plain() {
+        let mut document = parse("a: 1\nb: 2\nc: 3\nd: 4\n");
+
+        document.set_value(0, "x: y").unwrap();
+        document.set_value(1, "").unwrap();
+        document.set_value(2, "#x").unwrap();
+        document.set_value(3, "-Xmx1g").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "a: \"x: y\"\nb: \"\"\nc: \"#x\"\nd: -Xmx1g\n"
+        );
+    }
+
+    #[test]
+    fn set_value_should_quote_values_resolved_to_other_types() {
+        let mut document = parse("a: x\nb: x\nc: x\nd: x\ne: x\nf: true\ng: 0644\nh: [x]\n");
+
+        document.set_value(0, "null").unwrap();
+        document.set_value(1, "~").unwrap();
+        document.set_value(2, "off").unwrap();
+        document.set_value(3, "007").unwrap();
+        document.set_value(4, "8080").unwrap();
+        document.set_value(5, "no").unwrap();
+        document.set_value(6, "0755").unwrap();
+        document.set_value(7, "true").unwrap();
+
+        assert_eq!(
+            write(&document),
+            "a: \"null\"\nb: \"~\"\nc: \"off\"\nd: \"007\"\ne: 8080\nf: no\ng: 0755\nh: [\"true\"]\n"
+        );
+    }
+
+    #[test]
+    fn set_value_should_replace_entries_of_flow_collections() {
+        let mut document = parse("a: [1, 'x', {k: v, n:}] # c\n");
+
+        document.set_value(0, "100").unwrap();
+        document.set_value(1, "y").unwrap();
+        document.set_value(2, "a, b").unwrap();
+        document.set_value(3, "1").unwrap();
+
+        assert_eq!(write(&document), "a: [100, 'y', {k: \"a, b\", n: 1}] # c\n");
+    }
+
+    #[test]
+    fn set_value_should_fill_null_values() {
+        let mut document = parse("a:\nb: # comment\n");
+
+        document.set_value(0, "1").unwrap();
+        document.set_value(1, "2").unwrap();
+
+        assert_eq!(write(&document), "a: 1\nb: 2 # comment\n");
+    }
+
+    #[test]
+    fn set_value_should_replace_block_scalars() {
+        let mut document = parse("a: |\n  one\n  two\nb: 1\n");
+
+        document.set_value(0, "x").unwrap();
+
+        assert_eq!(write(&document), "a: x\nb: 1\n");
+    }
+
+    #[test]
+    fn push_should_insert_into_deepest_existing_collection() {
+        let mut document = parse(
+            "server:\n    ssl:\n        enabled: true\n    # comment\nlist:\n  - a\nother: 1\n",
+        );
+
+        document.push(Property::new("server.port", "8080")).unwrap();
+        document
+            .push(Property::new("server.ssl.key.alias", "k"))
+            .unwrap();
+        document
+            .push(Property::new("server.ssl.key.password", "p"))
+            .unwrap();
+        document.push(Property::new("list[1]", "b")).unwrap();
+        document
+            .push(Property::new("new.nested[0].name", "n"))
+            .unwrap();
+
+        assert_eq!(
+            write(&document),
+            "server:\n    ssl:\n        enabled: true\n        key:\n            alias: k\n            password: p\n    port: 8080\n    # comment\nlist:\n  - a\n  - b\nother: 1\nnew:\n    nested:\n        - name: n\n"
+        );
+    }
+
+    #[test]
+    fn push_should_insert_entries_into_flow_collections() {
+        let mut document = parse("a:\n  list: [1, {x: 1}]\n  map: {k: v}\n  empty: []\n");
+
+        document.push(Property::new("a.list[2]", "2")).unwrap();
+        document.push(Property::new("a.list[1].y", "a,b")).unwrap();
+        document.push(Property::new("a.map.n.m", "w")).unwrap();
+        document.push(Property::new("a.empty[0].k", "e")).unwrap();
+        document.push(Property::new("a.list.k", "f")).unwrap();
+
+        assert_eq!(
+            write(&document),
+            "a:\n  list: [1, {x: 1, y: \"a,b\"}, 2]\n  map: {k: v, n: {m: w}}\n  empty: [{k: e}]\n  list.k: f\n"
+        );
+        assert_eq!(
+            properties(&parse(&write(&document))),
+            pairs(&[
+                ("a.list[0]", "1"),
+                ("a.list[1].x", "1"),
+                ("a.list[1].y", "a,b"),
+                ("a.list[2]", "2"),
+                ("a.map.k", "v"),
+                ("a.map.n.m", "w"),
+                ("a.empty[0].k", "e"),
+                ("a.list.k", "f"),
+            ])
+        );
+    }
+
+    #[test]
+    fn push_should_add_keys_nested_under_scalars_as_flat_keys() {
+        let mut document = parse("a: 1\n");
+
+        document.push(Property::new("a.b", "2")).unwrap();
+
+        assert_eq!(write(&document), "a: 1\na.b: 2\n");
+    }
+
+    #[test]
+    fn push_should_reject_keys_of_another_kind_than_root() {
+        let mut sequence = parse("- a\n- b\n");
+        let mut mapping = parse("a: 1\n");
+
+        assert_eq!(
+            sequence
+                .push(Property::new("c.d", "1"))
+                .unwrap_err()
+                .to_string(),
+            "property 'c.d' cannot be represented in the yaml format: the root of the document is a sequence"
+        );
+        assert!(mapping.push(Property::new("[0]", "x")).is_err());
+        sequence.push(Property::new("[2]", "c")).unwrap();
+
+        assert_eq!(write(&sequence), "- a\n- b\n- c\n");
+        assert_eq!(write(&mapping), "a: 1\n");
+    }
+
+    #[test]
+    fn push_should_insert_into_first_document() {
+        let mut document = parse("a:\n  b: 1\n---\nc: 2\n");
+
+        document.push(Property::new("a.d", "x")).unwrap();
+        document.push(Property::new("e", "y")).unwrap();
+
+        assert_eq!(write(&document), "a:\n  b: 1\n  d: x\ne: y\n---\nc: 2\n");
+    }
+
+    #[test]
+    fn push_should_build_document_from_empty_input() {
+        let mut document = parse("");
+
+        document.push(Property::new("a.b", "1")).unwrap();
+        document.push(Property::new("a.c[0]", "x y")).unwrap();
+
+        assert_eq!(write(&document), "a:\n  b: 1\n  c:\n    - x y\n");
+    }
+}